@@ -0,0 +1,62 @@
+//! An autopilot that steers every snake with `--bot`, restarting after each
+//! round, so the game can be left running to soak-test it.
+
+use bevy::prelude::*;
+
+use crate::{
+    components::{Direction, Food, Obstacle, Portal, Position, SnakeHead, SnakeSegment},
+    config::ArenaConfig,
+    resources::{GameRules, Score},
+    GameState,
+};
+
+/// Steers the snakes in place of the players while present.
+#[derive(Resource)]
+pub struct Bot;
+
+/// Turns each head towards its nearest food, never into the arena edge, an
+/// obstacle or a snake. With no safe move left it keeps going straight.
+pub fn steer_bot(
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    mut heads: Query<(&mut SnakeHead, &Position)>,
+    segments: Query<&Position, With<SnakeSegment>>,
+    obstacles: Query<&Position, With<Obstacle>>,
+    food: Query<&Position, With<Food>>,
+    portals: Query<(&Position, &Portal)>,
+) {
+    for (mut head, position) in heads.iter_mut() {
+        let safe = Direction::ALL
+            .into_iter()
+            .filter(|direction| *direction != head.direction.opposite())
+            .map(|direction| {
+                let next = rules.advance_through(&arena, &portals, *position, direction);
+                (direction, next)
+            })
+            .filter(|(_, next)| {
+                arena.contains(next)
+                    && !segments
+                        .iter()
+                        .chain(obstacles.iter())
+                        .any(|cell| cell == next)
+            });
+        let distance_to_food = |cell: &Position| {
+            food.iter()
+                .map(|food| (food.x - cell.x).abs() + (food.y - cell.y).abs())
+                .min()
+                .unwrap_or(0)
+        };
+        if let Some((direction, _)) = safe.min_by_key(|(_, next)| distance_to_food(next)) {
+            head.direction = direction;
+        }
+    }
+}
+
+pub fn log_bot_score(score: Res<Score>) {
+    info!("Bot round over with a score of {}", score.0);
+}
+
+/// Skips the menu and end screens.
+pub fn start_bot_round(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
@@ -0,0 +1,289 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ArenaConfig,
+    resources::{DifficultyLevel, GameMode, Theme},
+    SECOND_SNAKE_HEAD_COLOR, SECOND_SNAKE_SEGMENT_COLOR,
+};
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+pub const ARROW_KEYS: [(KeyCode, Direction); 4] = [
+    (KeyCode::ArrowUp, Direction::Up),
+    (KeyCode::ArrowDown, Direction::Down),
+    (KeyCode::ArrowRight, Direction::Right),
+    (KeyCode::ArrowLeft, Direction::Left),
+];
+
+/// Player two in versus can use either hand's cluster.
+pub const PLAYER_TWO_KEYS: [(KeyCode, Direction); 8] = [
+    (KeyCode::KeyW, Direction::Up),
+    (KeyCode::KeyI, Direction::Up),
+    (KeyCode::KeyS, Direction::Down),
+    (KeyCode::KeyK, Direction::Down),
+    (KeyCode::KeyD, Direction::Right),
+    (KeyCode::KeyL, Direction::Right),
+    (KeyCode::KeyA, Direction::Left),
+    (KeyCode::KeyJ, Direction::Left),
+];
+
+/// A lone snake can be steered with either key set.
+pub const ALL_KEYS: [(KeyCode, Direction); 8] = [
+    (KeyCode::ArrowUp, Direction::Up),
+    (KeyCode::KeyW, Direction::Up),
+    (KeyCode::ArrowDown, Direction::Down),
+    (KeyCode::KeyS, Direction::Down),
+    (KeyCode::ArrowRight, Direction::Right),
+    (KeyCode::KeyD, Direction::Right),
+    (KeyCode::ArrowLeft, Direction::Left),
+    (KeyCode::KeyA, Direction::Left),
+];
+
+pub const DPAD_BUTTONS: [(GamepadButtonType, Direction); 4] = [
+    (GamepadButtonType::DPadUp, Direction::Up),
+    (GamepadButtonType::DPadDown, Direction::Down),
+    (GamepadButtonType::DPadRight, Direction::Right),
+    (GamepadButtonType::DPadLeft, Direction::Left),
+];
+
+impl Player {
+    pub fn number(self) -> u8 {
+        match self {
+            Self::One => 1,
+            Self::Two => 2,
+        }
+    }
+
+    /// Key bindings in priority order.
+    pub fn keys(self, mode: GameMode) -> &'static [(KeyCode, Direction)] {
+        match (mode, self) {
+            (GameMode::Single, _) => &ALL_KEYS,
+            (GameMode::Versus, Self::One) => &ARROW_KEYS,
+            (GameMode::Versus, Self::Two) => &PLAYER_TWO_KEYS,
+        }
+    }
+
+    /// Connected gamepads steering this player: a lone snake listens to all
+    /// of them, in versus each player gets one in connection order.
+    pub fn gamepads(self, mode: GameMode, gamepads: &Gamepads) -> Vec<Gamepad> {
+        let mut connected = gamepads.iter().collect::<Vec<Gamepad>>();
+        connected.sort_by_key(|gamepad| gamepad.id);
+        match mode {
+            GameMode::Single => connected,
+            GameMode::Versus => connected
+                .into_iter()
+                .nth(self.number() as usize - 1)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Head cell and heading. The players start in opposite corners, three
+    /// cells in, or closer to the edge on small arenas so they never share a
+    /// column or a row.
+    pub fn start(self, arena: &ArenaConfig) -> (Position, Direction) {
+        let inset = |size: u32| 3.min((size as i32 - 1) / 2 - 1);
+        let (x, y) = (inset(arena.width), inset(arena.height));
+        match self {
+            Self::One => (Position { x, y }, Direction::Up),
+            Self::Two => (
+                Position {
+                    x: arena.width as i32 - 1 - x,
+                    y: arena.height as i32 - 1 - y,
+                },
+                Direction::Down,
+            ),
+        }
+    }
+
+    pub fn head_color(self, theme: &Theme) -> Color {
+        match self {
+            Self::One => theme.head,
+            Self::Two => SECOND_SNAKE_HEAD_COLOR,
+        }
+    }
+
+    pub fn segment_color(self, theme: &Theme) -> Color {
+        match self {
+            Self::One => theme.segment,
+            Self::Two => SECOND_SNAKE_SEGMENT_COLOR,
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Default)]
+pub enum InputDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+}
+
+/// Device a snake was last steered with, so a key that is still held doesn't
+/// override a fresh gamepad press and vice versa.
+#[derive(Component, Default)]
+pub struct Steering {
+    pub device: InputDevice,
+    /// Stick direction on the previous frame, to tell a new push from a held one.
+    pub stick: Option<Direction>,
+}
+
+/// Cell a snake segment occupied before the last movement tick, so it can be
+/// drawn gliding towards its current [`Position`].
+#[derive(Component)]
+pub struct PreviousPosition(pub Position);
+
+#[derive(Component, Default)]
+pub struct LastTailPosition(pub Option<Position>);
+
+#[derive(Component)]
+pub struct SnakeSegment;
+
+/// Segments of a snake from head to tail, stored on its head.
+#[derive(Component, Default)]
+pub struct SnakeSegments(pub Vec<Entity>);
+
+/// Marks a head that ran into something on the last movement tick.
+#[derive(Component)]
+pub struct Crashed;
+
+/// Big "3", "2", "1" over a frozen round, gone with the countdown phase. Then
+/// another one reads "Go!" until its timer runs out.
+#[derive(Component)]
+pub struct CountdownText(pub Timer);
+
+/// Corner text with the lives left in a single player round.
+#[derive(Component)]
+pub struct LivesText;
+
+/// Menu button that picks its [`DifficultyLevel`] and starts a round.
+#[derive(Component)]
+pub struct DifficultyButton(pub DifficultyLevel);
+
+/// Screen text lines showing the current [`ArenaMode`] and [`GameMode`].
+#[derive(Component)]
+pub struct SettingsText;
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Right,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Self; 4] = [Self::Up, Self::Down, Self::Right, Self::Left];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Right => Self::Left,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+        }
+    }
+
+    /// Rotation, in radians, that turns an upward facing sprite this way.
+    pub fn angle(self) -> f32 {
+        match self {
+            Self::Up => 0.,
+            Self::Right => -FRAC_PI_2,
+            Self::Down => PI,
+            Self::Left => FRAC_PI_2,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Food;
+
+/// Checkerboard behind the arena, one sprite with a pixel per cell. It makes
+/// distances easier to judge and sets the arena apart from the letterbox
+/// margins. It lasts across rounds.
+#[derive(Component)]
+pub struct ArenaBackground;
+
+/// Faint line along a row or column boundary, laid over the checkerboard.
+/// Lines are numbered from the left or bottom edge, from 0 to the arena size.
+#[derive(Component)]
+pub struct GridLine {
+    pub vertical: bool,
+    pub index: u32,
+}
+
+/// Tile that ends the game when a head runs into it.
+#[derive(Component)]
+pub struct Obstacle;
+
+/// Part of the fixed wall pattern. Walls are also [`Obstacle`]s, so they block
+/// the snake and food alike.
+#[derive(Component)]
+pub struct Wall;
+
+/// One of a pair of linked cells. A head moving onto it comes out of the
+/// other one, at `exit`, and carries on in the same direction. Nothing else
+/// is ever placed on a portal.
+#[derive(Component)]
+pub struct Portal {
+    pub exit: Position,
+}
+
+/// Food worth extra points that disappears once its timer runs out.
+#[derive(Component)]
+pub struct BonusFood(pub Timer);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn step(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Up => Self {
+                y: self.y + 1,
+                ..self
+            },
+            Direction::Down => Self {
+                y: self.y - 1,
+                ..self
+            },
+            Direction::Right => Self {
+                x: self.x + 1,
+                ..self
+            },
+            Direction::Left => Self {
+                x: self.x - 1,
+                ..self
+            },
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Size {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Size {
+    pub fn square(x: f32) -> Self {
+        Self {
+            width: x,
+            height: x,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SnakeHead {
+    pub direction: Direction,
+}
@@ -0,0 +1,448 @@
+//! Arena options chosen at launch from `snake.toml` and the command line.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Position, BASE_MOVE_PERIOD, FOOD_COLOR, SNAKE_HEAD_COLOR, SNAKE_SEGMENT_COLOR,
+};
+
+/// Name of the optional config file, looked up next to the executable.
+pub const CONFIG_FILE_NAME: &str = "snake.toml";
+/// Smallest width or height an arena may have.
+pub const MIN_ARENA_SIZE: u32 = 5;
+/// Largest width or height an arena may have.
+pub const MAX_ARENA_SIZE: u32 = 100;
+pub const MIN_TICK_SECS: f32 = 0.02;
+pub const MAX_TICK_SECS: f32 = 1.;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct ArenaConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Seconds between movement ticks at the start of a round.
+    pub tick_secs: f32,
+    /// Obstacle tiles placed at the start of each round.
+    pub obstacles: u32,
+    /// Fixed walls laid out at the start of each round.
+    pub walls: WallPattern,
+    /// Colors of the first player's snake and of the food in the classic
+    /// [`Theme`](crate::resources::Theme).
+    pub head_color: Color,
+    pub segment_color: Color,
+    pub food_color: Color,
+}
+
+/// Layouts for the interior walls.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WallPattern {
+    #[default]
+    None,
+    /// A plus sign through the middle of the arena.
+    Cross,
+}
+
+impl FromStr for WallPattern {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "cross" => Ok(Self::Cross),
+            _ => Err(()),
+        }
+    }
+}
+
+impl WallPattern {
+    /// Cells the pattern covers in a `width` by `height` arena.
+    pub fn cells(self, width: u32, height: u32) -> Vec<Position> {
+        let (width, height) = (width as i32, height as i32);
+        match self {
+            Self::None => Vec::new(),
+            Self::Cross => {
+                let horizontal =
+                    (width / 4..width - width / 4).map(|x| Position { x, y: height / 2 });
+                let vertical = (height / 4..height - height / 4)
+                    .map(|y| Position { x: width / 2, y })
+                    .filter(|position| position.y != height / 2);
+                horizontal.chain(vertical).collect()
+            }
+        }
+    }
+}
+
+/// Values read from the config file; anything left out keeps its default.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ConfigFile {
+    width: Option<u32>,
+    height: Option<u32>,
+    speed: Option<f32>,
+    walls: Option<WallPattern>,
+    head_color: Option<String>,
+    segment_color: Option<String>,
+    food_color: Option<String>,
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 10,
+            tick_secs: BASE_MOVE_PERIOD.as_secs_f32(),
+            obstacles: 5,
+            walls: WallPattern::None,
+            head_color: SNAKE_HEAD_COLOR,
+            segment_color: SNAKE_SEGMENT_COLOR,
+            food_color: FOOD_COLOR,
+        }
+    }
+}
+
+impl ArenaConfig {
+    /// [`CONFIG_FILE_NAME`] in the directory holding the executable.
+    pub fn path() -> Option<PathBuf> {
+        let exe = std::env::current_exe().ok()?;
+        Some(exe.parent()?.join(CONFIG_FILE_NAME))
+    }
+
+    /// Applies the config file at `path` on top of `self`, keeping `self` when
+    /// the file is missing or malformed. Also returns a warning for every value
+    /// it had to ignore or clamp.
+    pub fn with_file(self, path: &Path) -> (Self, Vec<String>) {
+        let parsed = match fs::read_to_string(path) {
+            Ok(text) => self.with_toml(&text).map_err(|err| err.to_string()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return (self, Vec::new()),
+            Err(err) => Err(err.to_string()),
+        };
+        parsed.unwrap_or_else(|err| {
+            let warning = format!("ignoring {}: {err}", path.display());
+            (self, vec![warning])
+        })
+    }
+
+    /// Applies the values set in config file `text` on top of `self`.
+    pub fn with_toml(self, text: &str) -> Result<(Self, Vec<String>), toml::de::Error> {
+        let file: ConfigFile = toml::from_str(text)?;
+        let mut config = self;
+        let mut warnings = Vec::new();
+        if let Some(width) = file.width {
+            config.width = clamp(
+                "width",
+                width,
+                MIN_ARENA_SIZE,
+                MAX_ARENA_SIZE,
+                &mut warnings,
+            );
+        }
+        if let Some(height) = file.height {
+            config.height = clamp(
+                "height",
+                height,
+                MIN_ARENA_SIZE,
+                MAX_ARENA_SIZE,
+                &mut warnings,
+            );
+        }
+        match file.speed {
+            Some(speed) if speed.is_nan() => warnings.push("speed is not a number".to_string()),
+            Some(speed) => {
+                config.tick_secs =
+                    clamp("speed", speed, MIN_TICK_SECS, MAX_TICK_SECS, &mut warnings)
+            }
+            None => {}
+        }
+        if let Some(walls) = file.walls {
+            config.walls = walls;
+        }
+        let colors = [
+            ("head_color", file.head_color, &mut config.head_color),
+            (
+                "segment_color",
+                file.segment_color,
+                &mut config.segment_color,
+            ),
+            ("food_color", file.food_color, &mut config.food_color),
+        ];
+        for (field, hex, color) in colors {
+            let Some(hex) = hex else { continue };
+            match Srgba::hex(&hex) {
+                Ok(parsed) => *color = parsed.into(),
+                Err(err) => warnings.push(format!("{field} \"{hex}\" is not a color: {err}")),
+            }
+        }
+        Ok((config, warnings))
+    }
+
+    /// Applies `--width <cells>`, `--height <cells>`, `--speed <seconds>`,
+    /// `--obstacles <count>` and `--walls <pattern>` on top of `self`, ignoring
+    /// any other argument.
+    pub fn with_args(self, args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = self;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => config.width = parse_value(&arg, args.next())?,
+                "--height" => config.height = parse_value(&arg, args.next())?,
+                "--speed" => config.tick_secs = parse_value(&arg, args.next())?,
+                "--obstacles" => config.obstacles = parse_value(&arg, args.next())?,
+                "--walls" => config.walls = parse_value(&arg, args.next())?,
+                _ => {}
+            }
+        }
+        let sizes = MIN_ARENA_SIZE..=MAX_ARENA_SIZE;
+        if !sizes.contains(&config.width) || !sizes.contains(&config.height) {
+            return Err(format!(
+                "arena must be between {MIN_ARENA_SIZE}x{MIN_ARENA_SIZE} and \
+                 {MAX_ARENA_SIZE}x{MAX_ARENA_SIZE}, got {}x{}",
+                config.width, config.height
+            ));
+        }
+        if !(MIN_TICK_SECS..=MAX_TICK_SECS).contains(&config.tick_secs) {
+            return Err(format!(
+                "--speed must be between {MIN_TICK_SECS} and {MAX_TICK_SECS} seconds, got {}",
+                config.tick_secs
+            ));
+        }
+        if config.obstacles > config.width * config.height / 4 {
+            return Err(format!(
+                "--obstacles may cover at most a quarter of the arena, got {}",
+                config.obstacles
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Side, in pixels, of the largest square cell that fits the whole arena in
+    /// a window of `window_size`.
+    pub fn tile_size(&self, window_size: Vec2) -> f32 {
+        (window_size.x / self.width as f32).min(window_size.y / self.height as f32)
+    }
+
+    /// World space center of the cell at `position`, with the arena centered in
+    /// the window and letterboxed along its longer side.
+    pub fn cell_center(&self, position: &Position, window_size: Vec2) -> Vec2 {
+        let tile = self.tile_size(window_size);
+        let arena_size = Vec2::new(self.width as f32, self.height as f32) * tile;
+        (Vec2::new(position.x as f32, position.y as f32) + 0.5) * tile - arena_size / 2.
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        position.x >= 0
+            && position.y >= 0
+            && (position.x as u32) < self.width
+            && (position.y as u32) < self.height
+    }
+
+    /// Brings a position that left the arena back in from the opposite edge.
+    pub fn wrap(&self, position: Position) -> Position {
+        Position {
+            x: position.x.rem_euclid(self.width as i32),
+            y: position.y.rem_euclid(self.height as i32),
+        }
+    }
+
+    pub fn random_position(&self, rng: &mut impl Rng) -> Position {
+        Position {
+            x: rng.gen_range(0..self.width as i32),
+            y: rng.gen_range(0..self.height as i32),
+        }
+    }
+
+    /// Random position not in `occupied`, which must leave a cell free.
+    pub fn random_free_position(&self, rng: &mut impl Rng, occupied: &[Position]) -> Position {
+        loop {
+            let position = self.random_position(rng);
+            if !occupied.contains(&position) {
+                break position;
+            }
+        }
+    }
+}
+
+fn clamp<T: PartialOrd + std::fmt::Display + Copy>(
+    field: &str,
+    value: T,
+    min: T,
+    max: T,
+    warnings: &mut Vec<String>,
+) -> T {
+    let clamped = if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        return value;
+    };
+    warnings.push(format!(
+        "{field} {value} is outside {min}..={max}, using {clamped}"
+    ));
+    clamped
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{flag} needs a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {flag}: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ArenaConfig, String> {
+        ArenaConfig::default().with_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults_without_flags() {
+        assert_eq!(parse(&["--wrap"]), Ok(ArenaConfig::default()));
+    }
+
+    #[test]
+    fn reads_all_flags() {
+        let config = parse(&[
+            "--width",
+            "20",
+            "--height",
+            "15",
+            "--speed",
+            "0.07",
+            "--obstacles",
+            "3",
+            "--walls",
+            "cross",
+        ])
+        .unwrap();
+        assert_eq!(
+            config,
+            ArenaConfig {
+                width: 20,
+                height: 15,
+                tick_secs: 0.07,
+                obstacles: 3,
+                walls: WallPattern::Cross,
+                ..default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_small_or_malformed_values() {
+        assert!(parse(&["--width", "4"]).is_err());
+        assert!(parse(&["--height", "ten"]).is_err());
+        assert!(parse(&["--speed", "0"]).is_err());
+        assert!(parse(&["--speed"]).is_err());
+        assert!(parse(&["--obstacles", "26"]).is_err());
+        assert!(parse(&["--width", "101"]).is_err());
+        assert!(parse(&["--speed", "2"]).is_err());
+        assert!(parse(&["--walls", "maze"]).is_err());
+    }
+
+    #[test]
+    fn wraps_negative_positions() {
+        let config = ArenaConfig::default();
+        assert_eq!(
+            config.wrap(Position { x: -1, y: 10 }),
+            Position { x: 9, y: 0 }
+        );
+    }
+
+    #[test]
+    fn letterboxes_square_cells() {
+        let config = ArenaConfig::default();
+        let corner = Position { x: 0, y: 0 };
+        let next = Position { x: 1, y: 1 };
+        // Landscape: 50 pixel cells, with 250 pixel bars left and right.
+        let landscape = Vec2::new(1000., 500.);
+        assert_eq!(config.tile_size(landscape), 50.);
+        assert_eq!(
+            config.cell_center(&corner, landscape),
+            Vec2::new(-225., -225.)
+        );
+        assert_eq!(
+            config.cell_center(&next, landscape) - config.cell_center(&corner, landscape),
+            Vec2::splat(50.)
+        );
+        // Portrait: 30 pixel cells, with bars above and below.
+        let portrait = Vec2::new(300., 800.);
+        assert_eq!(config.tile_size(portrait), 30.);
+        assert_eq!(
+            config.cell_center(&corner, portrait),
+            Vec2::new(-135., -135.)
+        );
+    }
+
+    #[test]
+    fn reads_config_file() {
+        let (config, warnings) = ArenaConfig::default()
+            .with_toml(
+                r##"
+            width = 30
+            speed = 0.1
+            walls = "cross"
+            food_color = "#00ff00"
+            unknown = true
+            "##,
+            )
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(config.width, 30);
+        assert_eq!(config.height, 10);
+        assert_eq!(config.tick_secs, 0.1);
+        assert_eq!(config.walls, WallPattern::Cross);
+        assert_eq!(config.food_color, Color::srgb(0., 1., 0.));
+        assert_eq!(config.head_color, SNAKE_HEAD_COLOR);
+    }
+
+    #[test]
+    fn clamps_config_file_values() {
+        let (config, warnings) = ArenaConfig::default()
+            .with_toml(
+                r##"
+            width = 3
+            height = 500
+            speed = 0.001
+            head_color = "pink"
+            "##,
+            )
+            .unwrap();
+        assert_eq!(config.width, MIN_ARENA_SIZE);
+        assert_eq!(config.height, MAX_ARENA_SIZE);
+        assert_eq!(config.tick_secs, MIN_TICK_SECS);
+        assert_eq!(config.head_color, SNAKE_HEAD_COLOR);
+        let fields = ["width", "height", "speed", "head_color"];
+        assert_eq!(warnings.len(), fields.len());
+        for (warning, field) in warnings.iter().zip(fields) {
+            assert!(warning.starts_with(field), "{warning}");
+        }
+    }
+
+    #[test]
+    fn config_file_keeps_what_it_leaves_out() {
+        let base = ArenaConfig {
+            width: 20,
+            height: 15,
+            ..default()
+        };
+        let (config, _) = base.with_toml("height = 12").unwrap();
+        assert_eq!((config.width, config.height), (20, 12));
+    }
+
+    #[test]
+    fn rejects_malformed_config_file() {
+        assert!(ArenaConfig::default()
+            .with_toml("width = \"wide\"")
+            .is_err());
+    }
+}
@@ -0,0 +1,38 @@
+//! Files kept between runs in the platform directories: the high score, the
+//! last replay and the settings.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Location of the file `name` in the game's folder under `dir`, or at the
+/// path in `env_var` when that is set, mostly useful for testing.
+pub fn path(env_var: &str, dir: Option<PathBuf>, name: &str) -> Option<PathBuf> {
+    match std::env::var_os(env_var) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dir.map(|dir| dir.join("bevy-snake").join(name)),
+    }
+}
+
+/// Writes `contents` to `path`, creating its folder first.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+/// Reads a TOML file, reporting contents that don't parse as invalid data.
+pub fn read_toml<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_toml(path: &Path, value: &impl Serialize) -> io::Result<()> {
+    let text =
+        toml::to_string(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    write(path, text)
+}
@@ -0,0 +1,237 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    components::{BonusFood, Food, Obstacle, Player, Position, SnakeSegments},
+    config::ArenaConfig,
+    resources::{ArenaMode, GameRules, MoveCandidates, MoveTimer},
+    systems::{solid_cells, update_move_candidates},
+};
+
+const HEAD_HITBOX_COLOR: Color = Color::srgb(0., 1., 1.);
+const BODY_HITBOX_COLOR: Color = Color::srgb(1., 1., 0.);
+/// Tail that moves out of the way on the coming tick, so heads can follow it.
+const VACATING_TAIL_COLOR: Color = Color::srgb(0.5, 0.5, 0.);
+const SAFE_CANDIDATE_COLOR: Color = Color::srgb(0., 1., 0.);
+const LETHAL_COLOR: Color = Color::srgb(1., 0., 0.);
+
+/// Draws collision outlines over the arena, toggled at runtime with F2.
+pub struct HitboxPlugin;
+
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HitboxVisualization(false)).add_systems(
+            Update,
+            (
+                toggle_hitbox_visualization,
+                (draw_arena_edge, draw_hitboxes.after(update_move_candidates))
+                    .run_if(|visualization: Res<HitboxVisualization>| visualization.0),
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct HitboxVisualization(bool);
+
+fn toggle_hitbox_visualization(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut visualization: ResMut<HitboxVisualization>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        visualization.0 = !visualization.0;
+    }
+}
+
+/// Outlines the arena edge just inside, so it stays visible, while leaving it
+/// is lethal.
+fn draw_arena_edge(
+    mut gizmos: Gizmos,
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if rules.arena_mode != ArenaMode::Walled {
+        return;
+    }
+    let tile = arena.tile_size(window.size());
+    let arena_size = Vec2::new(arena.width as f32, arena.height as f32) * tile;
+    gizmos.rect_2d(Vec2::ZERO, 0., arena_size - Vec2::splat(2.), LETHAL_COLOR);
+}
+
+/// Obstacles and anything a snake can eat.
+type ObstacleOrFood = Or<(With<Obstacle>, With<Food>, With<BonusFood>)>;
+
+fn draw_hitboxes(
+    mut gizmos: Gizmos,
+    arena: Res<ArenaConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    move_candidates: Res<MoveCandidates>,
+    snakes: Query<&SnakeSegments>,
+    positions: Query<&Position>,
+    board: Query<(&Position, Has<Obstacle>), ObstacleOrFood>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let tile = Vec2::splat(arena.tile_size(window.size()));
+    let center = |position: &Position| arena.cell_center(position, window.size());
+    let obstacles = board
+        .iter()
+        .filter(|(_, is_obstacle)| *is_obstacle)
+        .map(|(position, _)| *position)
+        .collect::<Vec<_>>();
+    let food = board
+        .iter()
+        .filter(|(_, is_obstacle)| !is_obstacle)
+        .map(|(position, _)| *position)
+        .collect::<Vec<_>>();
+    for position in &obstacles {
+        gizmos.rect_2d(center(position), 0., tile * 0.9, LETHAL_COLOR);
+    }
+    // The same cells snake_movement checks, from the same candidates.
+    let moves = move_candidates
+        .0
+        .iter()
+        .filter_map(|(head, next)| {
+            let cells = snakes
+                .get(*head)
+                .ok()?
+                .0
+                .iter()
+                .map(|segment| positions.get(*segment).ok().copied())
+                .collect::<Option<Vec<Position>>>()?;
+            Some((cells, next))
+        })
+        .collect::<Vec<_>>();
+    let solid = solid_cells(
+        moves.iter().map(|(cells, next)| (cells.as_slice(), *next)),
+        &food,
+    );
+    for ((cells, _), solid) in moves.iter().zip(&solid) {
+        for (i, position) in cells.iter().enumerate() {
+            let color = match i {
+                0 => HEAD_HITBOX_COLOR,
+                _ if i >= solid.len() => VACATING_TAIL_COLOR,
+                _ => BODY_HITBOX_COLOR,
+            };
+            gizmos.rect_2d(center(position), 0., tile * 0.9, color);
+        }
+    }
+    for (_, candidate) in moves.iter() {
+        let lethal = !arena.contains(candidate)
+            || obstacles.contains(candidate)
+            || solid.iter().any(|cells| cells.contains(candidate));
+        let color = if lethal {
+            LETHAL_COLOR
+        } else {
+            SAFE_CANDIDATE_COLOR
+        };
+        gizmos.rect_2d(center(candidate), 0., tile * 0.7, color);
+    }
+}
+
+/// Shows frame rate, move period and each snake's length and head cell in the
+/// top left corner, toggled at runtime with F3.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.insert_resource(DebugOverlay(false)).add_systems(
+            Update,
+            (
+                toggle_debug_overlay,
+                show_debug_overlay
+                    .after(toggle_debug_overlay)
+                    .run_if(resource_changed::<DebugOverlay>),
+                update_debug_overlay
+                    .after(show_debug_overlay)
+                    .run_if(|overlay: Res<DebugOverlay>| overlay.0),
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct DebugOverlay(bool);
+
+#[derive(Component)]
+struct DebugOverlayText;
+
+fn toggle_debug_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+fn show_debug_overlay(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    texts: Query<Entity, With<DebugOverlayText>>,
+) {
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !overlay.0 {
+        return;
+    }
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.,
+                color: Color::srgb(0.8, 0.8, 0.8),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.),
+            left: Val::Px(4.),
+            ..default()
+        }),
+        DebugOverlayText,
+    ));
+}
+
+fn update_debug_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    move_timer: Res<MoveTimer>,
+    heads: Query<(&Player, &SnakeSegments, &Position)>,
+    mut texts: Query<&mut Text, With<DebugOverlayText>>,
+) {
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or_default();
+    let mut lines = vec![
+        format!("FPS: {fps:.0}"),
+        format!("Move period: {} ms", move_timer.0.duration().as_millis()),
+    ];
+    let mut heads = heads.iter().collect::<Vec<_>>();
+    heads.sort_by_key(|(player, _, _)| player.number());
+    for (player, segments, position) in heads {
+        lines.push(format!(
+            "P{}: length {} at ({}, {})",
+            player.number(),
+            segments.0.len(),
+            position.x,
+            position.y
+        ));
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+#[derive(Event)]
+pub struct GameOverEvent;
+
+#[derive(Event)]
+pub struct SpawnEvent;
+
+/// Sent when a lone snake crashed with lives to spare.
+#[derive(Event)]
+pub struct RespawnEvent;
+
+/// Sent when a snake is about to move in a different direction than on its
+/// last tick.
+#[derive(Event)]
+pub struct TurnEvent;
+
+/// Sent when snakes cover every cell the obstacles leave free.
+#[derive(Event)]
+pub struct WinEvent;
+
+#[derive(Event)]
+pub struct GrowthEvent {
+    /// Head of the snake that ate.
+    pub snake: Entity,
+    /// Segments to add to its tail.
+    pub segments: u32,
+}
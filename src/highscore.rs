@@ -0,0 +1,64 @@
+//! Reading and writing the best score as a plain-text file.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::datafile;
+
+/// Overrides [`path`].
+pub const PATH_ENV_VAR: &str = "SNAKE_HIGH_SCORE_FILE";
+
+/// Location of the high score file, in the platform data directory.
+pub fn path() -> Option<PathBuf> {
+    datafile::path(PATH_ENV_VAR, dirs::data_dir(), "high_score.txt")
+}
+
+pub fn load(path: &Path) -> io::Result<u32> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn save(path: &Path, score: u32) -> io::Result<()> {
+    datafile::write(path, score.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("bevy-snake-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = temp_path("round_trip.txt");
+        save(&path, 42).unwrap();
+        assert_eq!(load(&path).unwrap(), 42);
+        save(&path, 7).unwrap();
+        assert_eq!(load(&path).unwrap(), 7);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_file_is_not_found() {
+        let err = load(&temp_path("missing.txt")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn corrupt_file_is_invalid_data() {
+        let path = temp_path("corrupt.txt");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "not a number").unwrap();
+        let err = load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,410 @@
+//! Snake game logic, packaged as [`SnakePlugin`] so it can run in a window or
+//! headless in tests.
+
+use std::time::Duration;
+
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
+
+use bot::*;
+use config::ArenaConfig;
+use events::*;
+use replay::*;
+use resources::*;
+use settings::{save_settings, settings_saved};
+use stats::*;
+use systems::*;
+
+pub mod bot;
+pub mod components;
+pub mod config;
+pub mod datafile;
+#[cfg(feature = "debug")]
+pub mod debug;
+pub mod events;
+pub mod highscore;
+pub mod replay;
+pub mod resources;
+pub mod settings;
+pub mod stats;
+pub mod systems;
+
+pub const SNAKE_HEAD_COLOR: Color = Color::srgb(0.7, 0.7, 0.7);
+pub const SNAKE_SEGMENT_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+pub const SECOND_SNAKE_HEAD_COLOR: Color = Color::srgb(0.4, 0.8, 0.8);
+pub const SECOND_SNAKE_SEGMENT_COLOR: Color = Color::srgb(0.15, 0.4, 0.4);
+pub const SNAKE_EYE_COLOR: Color = Color::srgb(0.05, 0.05, 0.05);
+pub const FOOD_COLOR: Color = Color::srgb(1., 0., 1.);
+pub const BONUS_FOOD_COLOR: Color = Color::srgb(1., 0.84, 0.);
+/// Clear color of the default [`Theme`].
+pub const BACKGROUND_COLOR: Color = Color::srgb(0.04, 0.04, 0.04);
+pub const ARENA_COLOR: Color = Color::srgb(0.1, 0.1, 0.1);
+pub const ARENA_ALT_COLOR: Color = Color::srgb(0.12, 0.12, 0.12);
+pub const GRID_COLOR: Color = Color::srgba(1., 1., 1., 0.06);
+/// Thickness of a grid line, in pixels.
+pub const GRID_LINE_WIDTH: f32 = 1.;
+pub const BUTTON_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const BUTTON_HOVERED_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+pub const BUTTON_PRESSED_COLOR: Color = Color::srgb(0.35, 0.55, 0.35);
+/// Button of the difficulty currently picked.
+pub const BUTTON_SELECTED_COLOR: Color = Color::srgb(0.2, 0.35, 0.2);
+pub const OBSTACLE_COLOR: Color = Color::srgb(0.45, 0.08, 0.08);
+pub const WALL_COLOR: Color = Color::srgb(0.35, 0.3, 0.25);
+pub const PORTAL_COLOR: Color = Color::srgb(0.2, 0.45, 1.);
+pub const INITIAL_SNAKE_LENGTH: usize = 2;
+pub const BASE_MOVE_PERIOD: Duration = Duration::from_millis(150);
+pub const MOVE_PERIOD_STEP: Duration = Duration::from_millis(10);
+pub const FOODS_PER_SPEED_STEP: u32 = 2;
+pub const MIN_MOVE_PERIOD: Duration = Duration::from_millis(50);
+/// Default for [`StickDeadzone`].
+pub const STICK_DEADZONE: f32 = 0.5;
+pub const FOOD_POINTS: u32 = 1;
+pub const BONUS_FOOD_POINTS: u32 = 5;
+pub const FOOD_SEGMENTS: u32 = 1;
+/// Most regular foods `--food` may keep on the board at once.
+pub const MAX_FOOD_COUNT: usize = 5;
+pub const BONUS_FOOD_SEGMENTS: u32 = 2;
+/// Normal foods to eat before a bonus food shows up.
+pub const BONUS_FOOD_INTERVAL: u32 = 5;
+pub const BONUS_FOOD_LIFETIME: Duration = Duration::from_secs(5);
+/// Length the end screens time a snake to.
+pub const MILESTONE_LENGTH: usize = 10;
+
+/// How long a fresh round stays frozen before the snakes start moving.
+pub const COUNTDOWN: Duration = Duration::from_secs(3);
+/// Countdown a lone snake waits through after losing a life.
+pub const RESPAWN_DELAY: Duration = Duration::from_secs(1);
+/// Default for [`StartingLives`].
+pub const STARTING_LIVES: u32 = 3;
+/// How long "Go!" stays up once the countdown is over.
+pub const GO_DISPLAY: Duration = Duration::from_millis(600);
+
+pub const EAT_SOUND_PATH: &str = "sounds/eat.wav";
+pub const DEATH_SOUND_PATH: &str = "sounds/death.wav";
+pub const TURN_SOUND_PATH: &str = "sounds/turn.wav";
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    Menu,
+    Playing,
+    GameOver,
+    Won,
+}
+
+/// Phase of a round in progress. Rounds, restarts and respawns start out in
+/// the countdown, with the snakes held in place.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(GameState = GameState::Playing)]
+pub enum RoundPhase {
+    #[default]
+    Countdown,
+    Running,
+}
+
+/// Registers every game system, event and resource. Insert [`ArenaConfig`],
+/// [`GameRules`], [`GameRng`], [`FoodCount`], [`StickDeadzone`], [`Theme`],
+/// [`ShowGrid`], [`SoundSettings`], [`StartCountdown`] or [`StartingLives`]
+/// before adding the plugin to override their defaults. Inserting
+/// [`Settings`](settings::Settings) as well saves them whenever they change.
+pub struct SnakePlugin;
+
+impl Plugin for SnakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .add_sub_state::<RoundPhase>()
+            .enable_state_scoped_entities::<GameState>()
+            .enable_state_scoped_entities::<RoundPhase>()
+            .init_resource::<GameRng>()
+            .init_resource::<MoveCandidates>()
+            .init_resource::<GameRules>()
+            .init_resource::<GameMode>()
+            .init_resource::<ArenaConfig>()
+            .init_resource::<DifficultyLevel>()
+            .init_resource::<Difficulty>()
+            .init_resource::<MoveTimer>()
+            .init_resource::<Score>()
+            .init_resource::<HighScore>()
+            .init_resource::<FoodCount>()
+            .init_resource::<StickDeadzone>()
+            .init_resource::<FoodsSinceBonus>()
+            .init_resource::<Recording>()
+            .init_resource::<Theme>()
+            .init_resource::<ShowGrid>()
+            .init_resource::<SoundSettings>()
+            .init_resource::<StartCountdown>()
+            .init_resource::<StartingLives>()
+            .init_resource::<Lives>()
+            .init_resource::<RoundStats>()
+            .init_resource::<SessionBest>()
+            .add_systems(
+                Startup,
+                (
+                    log_seed,
+                    setup_camera,
+                    spawn_arena_background,
+                    spawn_grid_lines,
+                    load_sounds,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    drop_missing_sounds.run_if(
+                        resource_exists::<GameSounds>.and_then(resource_exists::<AssetServer>),
+                    ),
+                    cycle_theme,
+                    apply_theme
+                        .after(cycle_theme)
+                        .run_if(resource_changed::<Theme>),
+                    toggle_grid,
+                    toggle_mute,
+                    show_grid
+                        .after(toggle_grid)
+                        .run_if(resource_changed::<ShowGrid>),
+                    save_settings.run_if(settings_saved),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Menu),
+                (
+                    spawn_menu_screen,
+                    start_bot_round.run_if(resource_exists::<Bot>),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (
+                    start_round(),
+                    spawn_lives_text.run_if(resource_equals(GameMode::Single)),
+                    spawn_round_timer_text,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                OnEnter(RoundPhase::Countdown),
+                (spawn_countdown_text, rewind_move_timer),
+            )
+            .add_systems(OnEnter(RoundPhase::Running), spawn_go_text)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (
+                    end_round(),
+                    (spawn_game_over_screen, spawn_round_summary),
+                    restart_bot(),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                OnEnter(GameState::Won),
+                (
+                    end_round(),
+                    (spawn_win_screen, spawn_round_summary),
+                    restart_bot(),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    position_translation,
+                    size_scaling,
+                    rotate_heads,
+                    shade_segments,
+                    position_grid_lines,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_game,
+                    return_to_menu.run_if(not(in_state(GameState::Menu))),
+                    // A replay has to be watched with the settings it was recorded with.
+                    (
+                        select_difficulty,
+                        press_difficulty_button,
+                        toggle_arena_mode,
+                        toggle_portals,
+                        toggle_game_mode,
+                    )
+                        .run_if(not(resource_exists::<Playback>)),
+                    color_difficulty_buttons
+                        .after(select_difficulty)
+                        .after(press_difficulty_button),
+                    update_settings_text
+                        .after(select_difficulty)
+                        .after(press_difficulty_button)
+                        .after(toggle_arena_mode)
+                        .after(toggle_portals)
+                        .after(toggle_game_mode)
+                        .run_if(
+                            resource_changed::<GameRules>
+                                .or_else(resource_changed::<GameMode>)
+                                .or_else(resource_changed::<DifficultyLevel>),
+                        ),
+                )
+                    .run_if(
+                        in_state(GameState::Menu)
+                            .or_else(in_state(GameState::GameOver))
+                            .or_else(in_state(GameState::Won)),
+                    ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (save_own_high_score(), start_round())
+                        .chain()
+                        .before(tick_countdown)
+                        .before(finish_countdown)
+                        .before(tick_move_timer)
+                        .before(snake_movement_input)
+                        .run_if(restart_requested),
+                    (
+                        (
+                            tick_countdown,
+                            update_countdown_text.after(tick_countdown),
+                            finish_countdown.after(tick_countdown),
+                        )
+                            .run_if(in_state(RoundPhase::Countdown)),
+                        fade_go_text.run_if(in_state(RoundPhase::Running)),
+                        track_round_stats
+                            .after(snake_growth)
+                            .run_if(in_state(RoundPhase::Running)),
+                        update_round_timer_text.after(track_round_stats),
+                    ),
+                    // Everything that happens on a movement tick waits for the countdown.
+                    tick_move_timer
+                        .before(snake_movement)
+                        .run_if(in_state(RoundPhase::Running).and_then(not(playback_paused))),
+                    control_playback.before(tick_move_timer).run_if(
+                        in_state(RoundPhase::Running).and_then(resource_exists::<Playback>),
+                    ),
+                    snake_movement
+                        .after(tick_move_timer)
+                        .run_if(move_timer_finished.and_then(snakes_spawned)),
+                    snake_movement_input.before(snake_movement).run_if(
+                        not(resource_exists::<Playback>).and_then(not(resource_exists::<Bot>)),
+                    ),
+                    steer_bot
+                        .after(tick_move_timer)
+                        .after(snake_movement_input)
+                        .before(record_moves)
+                        .run_if(
+                            move_timer_finished
+                                .and_then(resource_exists::<Bot>)
+                                .and_then(not(resource_exists::<Playback>)),
+                        ),
+                    replay_moves
+                        .after(tick_move_timer)
+                        .run_if(move_timer_finished.and_then(resource_exists::<Playback>)),
+                    record_moves
+                        .after(tick_move_timer)
+                        .after(snake_movement_input)
+                        .after(replay_moves)
+                        .before(snake_movement)
+                        .run_if(move_timer_finished),
+                    update_move_candidates.after(snake_movement_input),
+                    (
+                        snake_eating.after(snake_movement),
+                        snake_growth.after(snake_eating),
+                    )
+                        .run_if(snakes_spawned),
+                    // A respawned snake is short again, so it slows back down.
+                    update_move_period
+                        .after(snake_growth)
+                        .after(respawn_snake)
+                        .run_if(on_event::<GrowthEvent>().or_else(on_event::<RespawnEvent>())),
+                    // After growth, so the new segments count as occupied. Growth
+                    // also comes from bonus food, which leaves the regular food in
+                    // place; topping up again is harmless.
+                    spawn_food
+                        .after(snake_growth)
+                        .run_if(on_event::<SpawnEvent>().or_else(on_event::<GrowthEvent>())),
+                    check_board_full
+                        .after(spawn_food)
+                        .run_if(on_event::<GrowthEvent>()),
+                    spawn_bonus_food.after(spawn_food),
+                    expire_bonus_food.before(record_moves),
+                    (
+                        // After growth, so segments added on the crash tick go too.
+                        lose_life
+                            .after(snake_growth)
+                            .run_if(resource_equals(GameMode::Single)),
+                        respawn_snake
+                            .after(lose_life)
+                            .before(spawn_food)
+                            .run_if(on_event::<RespawnEvent>()),
+                        update_lives_text
+                            .after(lose_life)
+                            .run_if(resource_changed::<Lives>),
+                    ),
+                    game_over
+                        .after(snake_movement)
+                        .after(check_board_full)
+                        .after(lose_life),
+                    send_turn_events
+                        .after(record_moves)
+                        .before(snake_movement)
+                        .run_if(move_timer_finished),
+                    (
+                        play_eat_sound.after(snake_eating),
+                        play_death_sound.after(snake_movement),
+                        play_turn_sound.after(send_turn_events),
+                    ),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_event::<GrowthEvent>()
+            .add_event::<SpawnEvent>()
+            .add_event::<GameOverEvent>()
+            .add_event::<WinEvent>()
+            .add_event::<TurnEvent>()
+            .add_event::<RespawnEvent>();
+    }
+}
+
+/// Clears the board and sets up a fresh round. Shared by starting a game and
+/// restarting one mid-round, so both reset the same things.
+fn start_round() -> SystemConfigs {
+    (
+        despawn_board,
+        start_recording,
+        reset_score,
+        reset_lives,
+        reset_round_stats,
+        reset_move_timer,
+        emit_spawn_signal,
+        restart_countdown,
+        spawn_snake,
+        spawn_walls,
+        spawn_obstacles,
+        spawn_portals,
+    )
+        .chain()
+}
+
+/// Saves what a finished round leaves behind, whether it was lost or won. A
+/// replay being watched saves nothing, and the bot's scores don't count.
+fn end_round() -> SystemConfigs {
+    (
+        save_own_high_score(),
+        save_replay.run_if(not(resource_exists::<Playback>)),
+    )
+        .into_configs()
+}
+
+/// Only single player rounds played by hand count towards the high score,
+/// not replays or the bot.
+fn save_own_high_score() -> SystemConfigs {
+    save_high_score.run_if(
+        resource_equals(GameMode::Single)
+            .and_then(not(resource_exists::<Bot>))
+            .and_then(not(resource_exists::<Playback>)),
+    )
+}
+
+/// Lets the bot play round after round on its own.
+fn restart_bot() -> SystemConfigs {
+    (log_bot_score, start_bot_round)
+        .chain()
+        .run_if(resource_exists::<Bot>)
+}
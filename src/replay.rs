@@ -0,0 +1,279 @@
+//! Recording the moves of each round, so a finished game can be watched again
+//! with `--replay <file>`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{BonusFood, Direction, Player, SnakeHead},
+    config::{ArenaConfig, WallPattern},
+    datafile,
+    resources::{ArenaMode, FoodCount, GameMode, GameRng, GameRules, MoveTimer},
+    GameState,
+};
+
+/// Overrides [`path`].
+pub const PATH_ENV_VAR: &str = "SNAKE_REPLAY_FILE";
+
+/// Everything needed to play a round again: the settings it started with and
+/// the direction of every snake on every movement tick.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// Seed [`GameRng`] is reset to when the round starts.
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    pub obstacles: u32,
+    pub walls: WallPattern,
+    pub arena_mode: ArenaMode,
+    /// Missing from replays saved before portals existed, which had none.
+    #[serde(default)]
+    pub portals: bool,
+    pub game_mode: GameMode,
+    pub food_count: usize,
+    /// Head directions in player order, one entry per movement tick.
+    pub moves: Vec<Vec<Direction>>,
+    /// Movement ticks after which a bonus food ran out. Its lifetime is
+    /// measured in real time, so it is recorded rather than replayed.
+    pub bonus_expiries: Vec<usize>,
+}
+
+impl Replay {
+    /// Rejects replays recorded on a different board, which would desync.
+    pub fn check_arena(&self, arena: &ArenaConfig) -> Result<(), String> {
+        if (self.width, self.height) != (arena.width, arena.height) {
+            return Err(format!(
+                "replay was recorded on a {}x{} arena, but this one is {}x{}",
+                self.width, self.height, arena.width, arena.height
+            ));
+        }
+        if (self.obstacles, self.walls) != (arena.obstacles, arena.walls) {
+            return Err(format!(
+                "replay was recorded with {} obstacles and {:?} walls, but this arena has {} and {:?}",
+                self.obstacles, self.walls, arena.obstacles, arena.walls
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Location of the last game, in the platform data directory.
+pub fn path() -> Option<PathBuf> {
+    datafile::path(PATH_ENV_VAR, dirs::data_dir(), "last_replay.toml")
+}
+
+pub fn load(path: &Path) -> io::Result<Replay> {
+    datafile::read_toml(path)
+}
+
+pub fn save(path: &Path, replay: &Replay) -> io::Result<()> {
+    datafile::write_toml(path, replay)
+}
+
+/// Reads the replay named by `--replay <file>`, if any.
+pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Replay>, String> {
+    let mut args = args.into_iter();
+    let Some(flag) = args.find(|arg| arg == "--replay") else {
+        return Ok(None);
+    };
+    let path = args.next().ok_or(format!("{flag} needs a value"))?;
+    load(Path::new(&path))
+        .map(Some)
+        .map_err(|err| format!("could not read replay {path}: {err}"))
+}
+
+/// The round being played, recorded as it goes.
+#[derive(Resource, Default)]
+pub struct Recording(pub Option<Replay>);
+
+/// A replay steering the snakes in place of the players.
+#[derive(Resource)]
+pub struct Playback {
+    pub replay: Replay,
+    /// Movement ticks played so far this round.
+    pub tick: usize,
+    /// Set while the replay only moves on when stepped.
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            tick: 0,
+            paused: false,
+        }
+    }
+}
+
+/// Reseeds [`GameRng`] for the round, so placements can be reproduced from
+/// the replay alone, and starts a new recording.
+pub fn start_recording(
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    mode: Res<GameMode>,
+    food_count: Res<FoodCount>,
+    mut rng: ResMut<GameRng>,
+    playback: Option<ResMut<Playback>>,
+    mut recording: ResMut<Recording>,
+) {
+    let seed = match playback {
+        Some(mut playback) => {
+            playback.tick = 0;
+            playback.replay.seed
+        }
+        None => rng.rng.gen(),
+    };
+    rng.rng = StdRng::seed_from_u64(seed);
+    recording.0 = Some(Replay {
+        seed,
+        width: arena.width,
+        height: arena.height,
+        obstacles: arena.obstacles,
+        walls: arena.walls,
+        arena_mode: rules.arena_mode,
+        portals: rules.portals,
+        game_mode: *mode,
+        food_count: food_count.0,
+        moves: Vec::new(),
+        bonus_expiries: Vec::new(),
+    });
+}
+
+pub fn record_moves(heads: Query<(&Player, &SnakeHead)>, mut recording: ResMut<Recording>) {
+    let Some(replay) = &mut recording.0 else {
+        return;
+    };
+    let mut heads = heads.iter().collect::<Vec<_>>();
+    heads.sort_by_key(|(player, _)| player.number());
+    replay
+        .moves
+        .push(heads.iter().map(|(_, head)| head.direction).collect());
+}
+
+pub fn record_bonus_expiry(recording: &mut Recording) {
+    if let Some(replay) = &mut recording.0 {
+        replay.bonus_expiries.push(replay.moves.len());
+    }
+}
+
+/// Steers every snake as recorded for this tick. A replay that runs out of
+/// moves without a crash ends the round rather than letting the snakes wander.
+pub fn replay_moves(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    mut heads: Query<(&Player, &mut SnakeHead)>,
+    bonus_food: Query<Entity, With<BonusFood>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let tick = playback.tick;
+    if playback.replay.bonus_expiries.contains(&tick) {
+        for entity in bonus_food.iter() {
+            commands.entity(entity).despawn();
+        }
+    }
+    let Some(moves) = playback.replay.moves.get(tick) else {
+        next_state.set(GameState::GameOver);
+        return;
+    };
+    for (player, mut head) in heads.iter_mut() {
+        if let Some(direction) = moves.get(player.number() as usize - 1) {
+            head.direction = *direction;
+        }
+    }
+    playback.tick += 1;
+}
+
+/// `Space` pauses and resumes a replay, and `.` plays a single movement tick
+/// while it is paused, so a crash can be looked at one tick at a time.
+pub fn control_playback(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    mut move_timer: ResMut<MoveTimer>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if !playback.paused {
+        return;
+    }
+    // Stands in for `tick_move_timer`: a step finishes the tick, and otherwise
+    // the tick that finished last frame is cleared.
+    let delta = if keyboard_input.just_pressed(KeyCode::Period) {
+        move_timer.0.remaining()
+    } else {
+        Duration::ZERO
+    };
+    move_timer.0.tick(delta);
+}
+
+pub fn playback_paused(playback: Option<Res<Playback>>) -> bool {
+    playback.is_some_and(|playback| playback.paused)
+}
+
+pub fn save_replay(recording: Res<Recording>) {
+    let (Some(replay), Some(path)) = (&recording.0, path()) else {
+        return;
+    };
+    if let Err(err) = save(&path, replay) {
+        warn!("Could not save replay to {}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn replay() -> Replay {
+        let arena = ArenaConfig::default();
+        Replay {
+            seed: 42,
+            width: arena.width,
+            height: arena.height,
+            obstacles: arena.obstacles,
+            walls: arena.walls,
+            arena_mode: ArenaMode::Wrapping,
+            portals: true,
+            game_mode: GameMode::Versus,
+            food_count: 1,
+            moves: vec![
+                vec![Direction::Up, Direction::Down],
+                vec![Direction::Left, Direction::Down],
+            ],
+            bonus_expiries: vec![1],
+        }
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = std::env::temp_dir()
+            .join(format!("bevy-snake-test-{}", std::process::id()))
+            .join("replay.toml");
+        save(&path, &replay()).unwrap();
+        assert_eq!(load(&path).unwrap(), replay());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_different_arena() {
+        let arena = ArenaConfig::default();
+        assert!(replay().check_arena(&arena).is_ok());
+        let wider = ArenaConfig { width: 20, ..arena };
+        let err = replay().check_arena(&wider).unwrap_err();
+        assert!(err.contains("10x10"), "{err}");
+        let walled = ArenaConfig {
+            walls: WallPattern::Cross,
+            ..arena
+        };
+        assert!(replay().check_arena(&walled).is_err());
+    }
+}
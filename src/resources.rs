@@ -0,0 +1,435 @@
+use std::{io, time::Duration};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Direction, Portal, Position},
+    config::ArenaConfig,
+    highscore, BACKGROUND_COLOR, COUNTDOWN, FOODS_PER_SPEED_STEP, MAX_FOOD_COUNT, MIN_MOVE_PERIOD,
+    MOVE_PERIOD_STEP, STARTING_LIVES, STICK_DEADZONE,
+};
+
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Single,
+    /// Two snakes on the same board, last one alive wins.
+    Versus,
+}
+
+#[derive(PartialEq, Copy, Clone, Default, Debug, Serialize, Deserialize)]
+pub enum ArenaMode {
+    /// Leaving the arena ends the game.
+    #[default]
+    Walled,
+    /// Leaving the arena re-enters it from the opposite edge.
+    Wrapping,
+}
+
+#[derive(Resource, Default)]
+pub struct GameRules {
+    pub arena_mode: ArenaMode,
+    /// Whether rounds start with a linked pair of [`Portal`]s on the board.
+    pub portals: bool,
+}
+
+impl GameRules {
+    /// `--wrap` wraps the arena and `--portals` adds portals, otherwise they
+    /// keep `arena_mode` and `portals`.
+    pub fn from_args(arena_mode: ArenaMode, portals: bool) -> Self {
+        let has_flag = |flag: &str| std::env::args().any(|arg| arg == flag);
+        Self {
+            arena_mode: if has_flag("--wrap") {
+                ArenaMode::Wrapping
+            } else {
+                arena_mode
+            },
+            portals: portals || has_flag("--portals"),
+        }
+    }
+
+    /// Direction that takes a head from `from` to the neighbouring `to`, if
+    /// they are neighbours.
+    pub fn direction_between(
+        &self,
+        arena: &ArenaConfig,
+        from: Position,
+        to: Position,
+    ) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .find(|direction| self.advance(arena, from, *direction) == to)
+    }
+
+    /// Position the head ends up in after moving one cell in `direction`.
+    pub fn advance(
+        &self,
+        arena: &ArenaConfig,
+        position: Position,
+        direction: Direction,
+    ) -> Position {
+        let next = position.step(direction);
+        match self.arena_mode {
+            ArenaMode::Walled => next,
+            ArenaMode::Wrapping => arena.wrap(next),
+        }
+    }
+
+    /// Like [`advance`](Self::advance), but a head moving onto one of the
+    /// `portals` comes out of the linked cell, one more cell along.
+    pub fn advance_through<'a>(
+        &self,
+        arena: &ArenaConfig,
+        portals: impl IntoIterator<Item = (&'a Position, &'a Portal)>,
+        position: Position,
+        direction: Direction,
+    ) -> Position {
+        let next = self.advance(arena, position, direction);
+        match portals.into_iter().find(|(entrance, _)| **entrance == next) {
+            Some((_, portal)) => self.advance(arena, portal.exit, direction),
+            None => next,
+        }
+    }
+}
+
+/// Tuning knobs for how fast the snake moves as it eats.
+#[derive(Resource)]
+pub struct Difficulty {
+    pub initial_period: Duration,
+    pub step: Duration,
+    pub foods_per_step: u32,
+    /// Fastest the snake speeds up to, unless it already starts faster.
+    pub min_period: Duration,
+}
+
+impl FromWorld for Difficulty {
+    fn from_world(world: &mut World) -> Self {
+        let arena = world
+            .get_resource::<ArenaConfig>()
+            .copied()
+            .unwrap_or_default();
+        let level = world
+            .get_resource::<DifficultyLevel>()
+            .copied()
+            .unwrap_or_default();
+        Self {
+            initial_period: Duration::from_secs_f32(arena.tick_secs * level.period_scale()),
+            step: level.period_step(),
+            foods_per_step: FOODS_PER_SPEED_STEP,
+            min_period: MIN_MOVE_PERIOD,
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn period(&self, foods_eaten: u32) -> Duration {
+        let steps = foods_eaten / self.foods_per_step.max(1);
+        self.initial_period
+            .saturating_sub(self.step * steps)
+            .max(self.min_period.min(self.initial_period))
+    }
+}
+
+/// Preset picked on the menu, with its buttons or the arrow keys. It sets how
+/// fast the snake starts out and speeds up, and whether the arena wraps
+/// around.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub enum DifficultyLevel {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl DifficultyLevel {
+    pub const ALL: [Self; 3] = [Self::Easy, Self::Normal, Self::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+
+    /// Starting move period, as a multiple of the arena's `tick_secs`.
+    pub fn period_scale(self) -> f32 {
+        match self {
+            Self::Easy => 1.5,
+            Self::Normal => 1.,
+            Self::Hard => 0.6,
+        }
+    }
+
+    /// How much faster the snake gets every few foods.
+    pub fn period_step(self) -> Duration {
+        match self {
+            Self::Easy => Duration::ZERO,
+            Self::Normal => MOVE_PERIOD_STEP,
+            Self::Hard => MOVE_PERIOD_STEP * 3 / 2,
+        }
+    }
+
+    pub fn arena_mode(self) -> ArenaMode {
+        match self {
+            Self::Easy => ArenaMode::Wrapping,
+            Self::Normal | Self::Hard => ArenaMode::Walled,
+        }
+    }
+
+    /// The neighbouring preset, `steps` away in [`Self::ALL`], stopping at
+    /// either end.
+    pub fn offset(self, steps: isize) -> Self {
+        let index = Self::ALL.iter().position(|level| *level == self).unwrap();
+        let index = index.saturating_add_signed(steps).min(Self::ALL.len() - 1);
+        Self::ALL[index]
+    }
+}
+
+#[derive(Resource)]
+pub struct MoveTimer(pub Timer);
+
+/// Lives a single player round starts with.
+#[derive(Resource)]
+pub struct StartingLives(pub u32);
+
+impl Default for StartingLives {
+    fn default() -> Self {
+        Self(STARTING_LIVES)
+    }
+}
+
+/// Lives left this round, counting the snake on the board.
+#[derive(Resource, Default)]
+pub struct Lives(pub u32);
+
+/// Freezes the snakes at the start of each round until it runs out. Its
+/// duration is kept across rounds, so a zero duration skips the countdown.
+#[derive(Resource)]
+pub struct StartCountdown(pub Timer);
+
+impl Default for StartCountdown {
+    fn default() -> Self {
+        Self(Timer::new(COUNTDOWN, TimerMode::Once))
+    }
+}
+
+impl FromWorld for MoveTimer {
+    fn from_world(world: &mut World) -> Self {
+        let period = world.resource::<Difficulty>().period(0);
+        Self(Timer::new(period, TimerMode::Repeating))
+    }
+}
+
+/// Environment variable that seeds [`GameRng`] when `--seed` isn't given.
+pub const SEED_ENV_VAR: &str = "SNAKE_SEED";
+
+/// Source of all gameplay randomness, so a run can be reproduced from its seed.
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seeds from `--seed <u64>`, then [`SEED_ENV_VAR`], then entropy.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let seed = match args.find(|arg| arg == "--seed") {
+            Some(flag) => Some(args.next().ok_or(format!("{flag} needs a value"))?),
+            None => std::env::var(SEED_ENV_VAR).ok(),
+        };
+        match seed {
+            Some(seed) => seed
+                .parse()
+                .map(Self::new)
+                .map_err(|_| format!("invalid seed: {seed}")),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// Cell each head, by entity, will move into on the next movement tick.
+#[derive(Resource, Default)]
+pub struct MoveCandidates(pub Vec<(Entity, Position)>);
+
+#[derive(Resource, Default)]
+pub struct Score(pub u32);
+
+/// Best score across runs, persisted through [`highscore`]. Initializing the
+/// resource loads it from disk.
+#[derive(Resource)]
+pub struct HighScore(pub u32);
+
+impl FromWorld for HighScore {
+    fn from_world(_: &mut World) -> Self {
+        Self::load()
+    }
+}
+
+impl HighScore {
+    pub fn load() -> Self {
+        let Some(path) = highscore::path() else {
+            return Self(0);
+        };
+        match highscore::load(&path) {
+            Ok(score) => Self(score),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self(0),
+            Err(err) => {
+                warn!("Ignoring high score file {}: {err}", path.display());
+                Self(0)
+            }
+        }
+    }
+}
+
+/// Regular foods kept on the board at once.
+#[derive(Resource)]
+pub struct FoodCount(pub usize);
+
+impl Default for FoodCount {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl FoodCount {
+    /// Applies `--food <count>`, from 1 to [`MAX_FOOD_COUNT`], on top of `self`.
+    pub fn with_args(self, args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let Some(flag) = args.find(|arg| arg == "--food") else {
+            return Ok(self);
+        };
+        let count = args.next().ok_or(format!("{flag} needs a value"))?;
+        match count.parse() {
+            Ok(count) if (1..=MAX_FOOD_COUNT).contains(&count) => Ok(Self(count)),
+            _ => Err(format!(
+                "{flag} must be between 1 and {MAX_FOOD_COUNT}, got {count}"
+            )),
+        }
+    }
+}
+
+/// How far the left stick has to be pushed, from 0 to 1, before it steers.
+#[derive(Resource)]
+pub struct StickDeadzone(pub f32);
+
+impl Default for StickDeadzone {
+    fn default() -> Self {
+        Self(STICK_DEADZONE)
+    }
+}
+
+/// Colors the board is drawn in. C cycles through [`Theme::presets`].
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Head and body of the first player's snake.
+    pub head: Color,
+    pub segment: Color,
+    pub food: Color,
+    /// Clear color around and behind the arena.
+    pub background: Color,
+}
+
+impl FromWorld for Theme {
+    fn from_world(world: &mut World) -> Self {
+        let arena = world
+            .get_resource::<ArenaConfig>()
+            .copied()
+            .unwrap_or_default();
+        Self::presets(&arena)[0]
+    }
+}
+
+impl Theme {
+    /// Built-in palettes. The first keeps the colors from the arena config.
+    pub fn presets(arena: &ArenaConfig) -> [Self; 3] {
+        [
+            Self {
+                name: "Classic",
+                head: arena.head_color,
+                segment: arena.segment_color,
+                food: arena.food_color,
+                background: BACKGROUND_COLOR,
+            },
+            Self {
+                name: "Forest",
+                head: Color::srgb(0.55, 0.8, 0.35),
+                segment: Color::srgb(0.2, 0.45, 0.15),
+                food: Color::srgb(0.95, 0.3, 0.2),
+                background: Color::srgb(0.02, 0.06, 0.03),
+            },
+            Self {
+                name: "Sunset",
+                head: Color::srgb(1., 0.7, 0.3),
+                segment: Color::srgb(0.6, 0.25, 0.15),
+                food: Color::srgb(0.4, 0.8, 1.),
+                background: Color::srgb(0.08, 0.03, 0.06),
+            },
+        ]
+    }
+
+    /// The preset after this one, wrapping around to the first.
+    pub fn next(&self, arena: &ArenaConfig) -> Self {
+        let presets = Self::presets(arena);
+        let index = presets
+            .iter()
+            .position(|preset| preset.name == self.name)
+            .map_or(0, |index| (index + 1) % presets.len());
+        presets[index]
+    }
+}
+
+/// Whether the grid lines over the arena are drawn. Toggled with G.
+#[derive(Resource)]
+pub struct ShowGrid(pub bool);
+
+impl Default for ShowGrid {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Sound effects, loaded once at startup. Removed again if any of them
+/// fails to load, which leaves the game silent.
+#[derive(Resource)]
+pub struct GameSounds {
+    pub eat: Handle<AudioSource>,
+    pub turn: Handle<AudioSource>,
+    pub death: Handle<AudioSource>,
+}
+
+/// Master volume for the sound effects. M toggles `muted`.
+#[derive(Resource)]
+pub struct SoundSettings {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.,
+            muted: false,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct FoodsSinceBonus(pub u32);
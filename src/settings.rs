@@ -0,0 +1,322 @@
+//! Choices made while playing, kept between sessions in a TOML file. It also
+//! holds the arena size and speed, which `snake.toml` (see [`ArenaConfig`])
+//! and the command line override; the wall pattern and colors only live there.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{ArenaConfig, MAX_ARENA_SIZE, MAX_TICK_SECS, MIN_ARENA_SIZE, MIN_TICK_SECS},
+    datafile,
+    replay::Playback,
+    resources::{ArenaMode, DifficultyLevel, FoodCount, GameRules, ShowGrid, SoundSettings, Theme},
+    MAX_FOOD_COUNT,
+};
+
+/// Overrides [`path`].
+pub const PATH_ENV_VAR: &str = "SNAKE_SETTINGS_FILE";
+
+/// Everything that is saved. Fields missing from the file keep their
+/// defaults, which match a fresh install, and unknown fields are ignored.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub arena_mode: ArenaMode,
+    pub portals: bool,
+    pub difficulty: DifficultyLevel,
+    /// Name of one of the [`Theme::presets`].
+    pub theme: String,
+    pub food_count: usize,
+    pub volume: f32,
+    pub muted: bool,
+    pub show_grid: bool,
+    pub width: u32,
+    pub height: u32,
+    /// Seconds per move, like [`ArenaConfig::tick_secs`].
+    pub speed: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            arena_mode: ArenaMode::default(),
+            portals: GameRules::default().portals,
+            difficulty: DifficultyLevel::default(),
+            theme: Theme::presets(&ArenaConfig::default())[0].name.to_string(),
+            food_count: FoodCount::default().0,
+            volume: SoundSettings::default().volume,
+            muted: SoundSettings::default().muted,
+            show_grid: ShowGrid::default().0,
+            width: ArenaConfig::default().width,
+            height: ArenaConfig::default().height,
+            speed: ArenaConfig::default().tick_secs,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings file, keeping the defaults when it is missing or
+    /// can't be used. Also returns a warning in the latter case, since this
+    /// runs before logging is set up.
+    pub fn load() -> (Self, Vec<String>) {
+        let Some(path) = path() else {
+            return Default::default();
+        };
+        match load(&path) {
+            Ok(settings) => (settings, Vec::new()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Default::default(),
+            Err(err) => {
+                let warning = format!("ignoring {}: {err}", path.display());
+                (Self::default(), vec![warning])
+            }
+        }
+    }
+
+    /// Copies into `self` every field that differs between `before` and
+    /// `after`, keeping the others.
+    pub fn apply_changes(&mut self, before: &Self, after: &Self) {
+        macro_rules! copy_changed {
+            ($($field:ident),*) => {
+                $(if before.$field != after.$field {
+                    self.$field = after.$field.clone();
+                })*
+            };
+        }
+        copy_changed!(
+            arena_mode, portals, difficulty, theme, food_count, volume, muted, show_grid, width,
+            height, speed
+        );
+    }
+
+    /// The arena to start from before `snake.toml` and the command line are
+    /// applied.
+    pub fn arena(&self) -> ArenaConfig {
+        ArenaConfig {
+            width: self.width,
+            height: self.height,
+            tick_secs: self.speed,
+            ..default()
+        }
+    }
+
+    /// The saved theme, drawn from `arena`'s presets.
+    pub fn theme(&self, arena: &ArenaConfig) -> Theme {
+        let presets = Theme::presets(arena);
+        presets
+            .into_iter()
+            .find(|preset| preset.name == self.theme)
+            .unwrap_or(presets[0])
+    }
+}
+
+/// Location of the settings file, in the platform config directory.
+pub fn path() -> Option<PathBuf> {
+    datafile::path(PATH_ENV_VAR, dirs::config_dir(), "settings.toml")
+}
+
+/// Reads the settings at `path`, clamping values that are out of range.
+pub fn load(path: &Path) -> io::Result<Settings> {
+    let mut settings: Settings = datafile::read_toml(path)?;
+    settings.food_count = settings.food_count.clamp(1, MAX_FOOD_COUNT);
+    settings.volume = settings.volume.clamp(0., 1.);
+    settings.width = settings.width.clamp(MIN_ARENA_SIZE, MAX_ARENA_SIZE);
+    settings.height = settings.height.clamp(MIN_ARENA_SIZE, MAX_ARENA_SIZE);
+    settings.speed = if settings.speed.is_nan() {
+        Settings::default().speed
+    } else {
+        settings.speed.clamp(MIN_TICK_SECS, MAX_TICK_SECS)
+    };
+    Ok(settings)
+}
+
+pub fn save(path: &Path, settings: &Settings) -> io::Result<()> {
+    datafile::write_toml(path, settings)
+}
+
+/// The resources [`Settings`] are taken from.
+#[derive(SystemParam)]
+pub struct LiveSettings<'w> {
+    rules: Res<'w, GameRules>,
+    level: Res<'w, DifficultyLevel>,
+    theme: Res<'w, Theme>,
+    food_count: Res<'w, FoodCount>,
+    sound: Res<'w, SoundSettings>,
+    show_grid: Res<'w, ShowGrid>,
+    arena: Res<'w, ArenaConfig>,
+}
+
+impl LiveSettings<'_> {
+    pub fn current(&self) -> Settings {
+        Settings {
+            arena_mode: self.rules.arena_mode,
+            portals: self.rules.portals,
+            difficulty: *self.level,
+            theme: self.theme.name.to_string(),
+            food_count: self.food_count.0,
+            volume: self.sound.volume,
+            muted: self.sound.muted,
+            show_grid: self.show_grid.0,
+            width: self.arena.width,
+            height: self.arena.height,
+            speed: self.arena.tick_secs,
+        }
+    }
+}
+
+/// Writes back the settings changed in game. Only runs while a [`Settings`]
+/// resource holds what is in the file, and not while watching a replay,
+/// which brings its own rules along. Values set on the command line differ
+/// from the file from the start, so they stay out of it unless they are
+/// changed again in game.
+pub fn save_settings(
+    mut saved: ResMut<Settings>,
+    live: LiveSettings,
+    mut previous: Local<Option<Settings>>,
+) {
+    let current = live.current();
+    let Some(before) = previous.replace(current.clone()) else {
+        return;
+    };
+    let mut updated = saved.clone();
+    updated.apply_changes(&before, &current);
+    if *saved == updated {
+        return;
+    }
+    *saved = updated;
+    if let Some(path) = path() {
+        if let Err(err) = save(&path, &saved) {
+            warn!("Could not save settings to {}: {err}", path.display());
+        }
+    }
+}
+
+/// Run condition for [`save_settings`].
+pub fn settings_saved(settings: Option<Res<Settings>>, playback: Option<Res<Playback>>) -> bool {
+    settings.is_some() && playback.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("bevy-snake-settings-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = temp_path("round_trip.toml");
+        let settings = Settings {
+            arena_mode: ArenaMode::Wrapping,
+            portals: true,
+            difficulty: DifficultyLevel::Hard,
+            theme: "Forest".to_string(),
+            food_count: 3,
+            volume: 0.5,
+            muted: true,
+            show_grid: false,
+            width: 20,
+            height: 15,
+            speed: 0.07,
+        };
+        save(&path, &settings).unwrap();
+        assert_eq!(load(&path).unwrap(), settings);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_and_unknown_fields_are_fine() {
+        let path = temp_path("partial.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            "food_count = 9\nwidth = 1000\nfavourite_fruit = \"plum\"\n",
+        )
+        .unwrap();
+        let settings = load(&path).unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                food_count: MAX_FOOD_COUNT,
+                width: MAX_ARENA_SIZE,
+                ..default()
+            }
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_file_is_invalid_data() {
+        let path = temp_path("corrupt.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "volume = \"loud\"").unwrap();
+        let err = load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn applies_only_changed_fields() {
+        let mut saved = Settings::default();
+        let before = Settings {
+            food_count: 3,
+            ..default()
+        };
+        let after = Settings {
+            muted: true,
+            ..before.clone()
+        };
+        saved.apply_changes(&before, &after);
+        assert_eq!(
+            saved,
+            Settings {
+                muted: true,
+                ..default()
+            }
+        );
+    }
+
+    #[test]
+    fn load_reports_an_unusable_file() {
+        let path = temp_path("unusable.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "muted = 3").unwrap();
+        std::env::set_var(PATH_ENV_VAR, &path);
+        let (settings, warnings) = Settings::load();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(settings, Settings::default());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unusable.toml"), "{warnings:?}");
+    }
+
+    #[test]
+    fn arena_size_and_speed_sit_under_the_config_file() {
+        let settings = Settings {
+            width: 20,
+            height: 15,
+            speed: 0.3,
+            ..default()
+        };
+        let (arena, _) = settings.arena().with_toml("width = 30").unwrap();
+        assert_eq!((arena.width, arena.height, arena.tick_secs), (30, 15, 0.3));
+    }
+
+    #[test]
+    fn unknown_theme_falls_back_to_the_first() {
+        let arena = ArenaConfig::default();
+        let settings = Settings {
+            theme: "Neon".to_string(),
+            ..default()
+        };
+        assert_eq!(settings.theme(&arena), Theme::presets(&arena)[0]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Per-round statistics: a running timer in the corner while playing, and a
+//! summary under the end screens.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{components::SnakeSegments, events::GrowthEvent, GameState, MILESTONE_LENGTH};
+
+/// What happened so far this round. Time only counts while the snakes move,
+/// so countdowns don't add to it.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct RoundStats {
+    pub elapsed: Duration,
+    pub foods_eaten: u32,
+    /// Longest any snake has been this round.
+    pub max_length: usize,
+    /// Round time when a snake first reached [`MILESTONE_LENGTH`].
+    pub milestone: Option<Duration>,
+}
+
+impl RoundStats {
+    pub fn tick(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+
+    pub fn eat(&mut self) {
+        self.foods_eaten += 1;
+    }
+
+    /// Notes a snake `length` segments long.
+    pub fn reach_length(&mut self, length: usize) {
+        self.max_length = self.max_length.max(length);
+        if self.milestone.is_none() && length >= MILESTONE_LENGTH {
+            self.milestone = Some(self.elapsed);
+        }
+    }
+
+    /// Lines shown under the end screens.
+    pub fn summary(&self, best: &SessionBest) -> String {
+        let time_or_dash = |time: Option<Duration>| time.map_or("-".to_string(), format_time);
+        format!(
+            "Time {}   Food {}   Longest {}\nTo length {MILESTONE_LENGTH}: {} (session best {})",
+            format_time(self.elapsed),
+            self.foods_eaten,
+            self.max_length,
+            time_or_dash(self.milestone),
+            time_or_dash(best.0),
+        )
+    }
+}
+
+/// Fastest time to [`MILESTONE_LENGTH`] since the game was launched.
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct SessionBest(pub Option<Duration>);
+
+impl SessionBest {
+    /// Keeps `time` if it beats the best so far.
+    pub fn offer(&mut self, time: Duration) {
+        if self.0.map_or(true, |best| time < best) {
+            self.0 = Some(time);
+        }
+    }
+}
+
+/// `mm:ss.t`, rounded down to the tenth of a second.
+pub fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!(
+        "{:02}:{:02}.{}",
+        tenths / 600,
+        tenths / 10 % 60,
+        tenths % 10
+    )
+}
+
+/// Corner text with the round time.
+#[derive(Component)]
+pub struct RoundTimerText;
+
+pub fn reset_round_stats(mut stats: ResMut<RoundStats>) {
+    *stats = RoundStats::default();
+}
+
+/// Counts time, food and length. Every growth comes from one food eaten.
+pub fn track_round_stats(
+    time: Res<Time>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    snakes: Query<&SnakeSegments>,
+    mut stats: ResMut<RoundStats>,
+    mut best: ResMut<SessionBest>,
+) {
+    stats.tick(time.delta());
+    for _ in growth_reader.read() {
+        stats.eat();
+    }
+    for segments in snakes.iter() {
+        stats.reach_length(segments.0.len());
+    }
+    if let Some(milestone) = stats.milestone {
+        best.offer(milestone);
+    }
+}
+
+pub fn spawn_round_timer_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            format_time(Duration::ZERO),
+            TextStyle {
+                font_size: 20.,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.),
+            width: Val::Percent(100.),
+            ..default()
+        })
+        .with_text_justify(JustifyText::Center),
+        RoundTimerText,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+pub fn update_round_timer_text(
+    stats: Res<RoundStats>,
+    mut texts: Query<&mut Text, With<RoundTimerText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format_time(stats.elapsed);
+    }
+}
+
+/// Puts the [`RoundStats::summary`] along the bottom of whichever end screen
+/// is showing.
+pub fn spawn_round_summary(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    stats: Res<RoundStats>,
+    best: Res<SessionBest>,
+) {
+    commands.spawn((
+        TextBundle::from_section(
+            stats.summary(&best),
+            TextStyle {
+                font_size: 18.,
+                color: Color::srgb(0.6, 0.6, 0.6),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.),
+            width: Val::Percent(100.),
+            ..default()
+        })
+        .with_text_justify(JustifyText::Center),
+        StateScoped(*state.get()),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_minutes_seconds_and_tenths() {
+        assert_eq!(format_time(Duration::ZERO), "00:00.0");
+        assert_eq!(format_time(Duration::from_millis(9_999)), "00:09.9");
+        assert_eq!(format_time(Duration::from_millis(83_450)), "01:23.4");
+        assert_eq!(format_time(Duration::from_secs(600)), "10:00.0");
+    }
+
+    #[test]
+    fn tracks_time_food_and_length() {
+        let mut stats = RoundStats::default();
+        stats.tick(Duration::from_secs(2));
+        stats.eat();
+        stats.eat();
+        stats.reach_length(4);
+        stats.reach_length(2);
+        assert_eq!(
+            stats,
+            RoundStats {
+                elapsed: Duration::from_secs(2),
+                foods_eaten: 2,
+                max_length: 4,
+                milestone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn milestone_keeps_the_first_time() {
+        let mut stats = RoundStats::default();
+        stats.tick(Duration::from_secs(5));
+        stats.reach_length(MILESTONE_LENGTH);
+        stats.tick(Duration::from_secs(5));
+        stats.reach_length(MILESTONE_LENGTH + 1);
+        assert_eq!(stats.milestone, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn session_best_keeps_the_fastest() {
+        let mut best = SessionBest::default();
+        best.offer(Duration::from_secs(30));
+        best.offer(Duration::from_secs(40));
+        best.offer(Duration::from_secs(20));
+        assert_eq!(best, SessionBest(Some(Duration::from_secs(20))));
+    }
+
+    #[test]
+    fn summary_shows_dashes_before_the_milestone() {
+        let stats = RoundStats {
+            elapsed: Duration::from_millis(12_300),
+            foods_eaten: 3,
+            max_length: 5,
+            milestone: None,
+        };
+        assert_eq!(
+            stats.summary(&SessionBest(Some(Duration::from_secs(45)))),
+            format!(
+                "Time 00:12.3   Food 3   Longest 5\nTo length {MILESTONE_LENGTH}: - (session best 00:45.0)"
+            ),
+        );
+    }
+}
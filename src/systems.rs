@@ -0,0 +1,1598 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::LoadState,
+    audio::Volume,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    utils::HashSet,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    components::{Direction, *},
+    config::ArenaConfig,
+    events::*,
+    highscore,
+    replay::{record_bonus_expiry, Playback, Recording},
+    resources::*,
+    GameState, RoundPhase, ARENA_ALT_COLOR, ARENA_COLOR, BONUS_FOOD_COLOR, BONUS_FOOD_INTERVAL,
+    BONUS_FOOD_LIFETIME, BONUS_FOOD_POINTS, BONUS_FOOD_SEGMENTS, BUTTON_COLOR,
+    BUTTON_HOVERED_COLOR, BUTTON_PRESSED_COLOR, BUTTON_SELECTED_COLOR, DEATH_SOUND_PATH,
+    EAT_SOUND_PATH, FOOD_POINTS, FOOD_SEGMENTS, GO_DISPLAY, GRID_COLOR, GRID_LINE_WIDTH,
+    INITIAL_SNAKE_LENGTH, OBSTACLE_COLOR, PORTAL_COLOR, RESPAWN_DELAY, SNAKE_EYE_COLOR,
+    TURN_SOUND_PATH, WALL_COLOR,
+};
+
+/// Tops the board back up to [`FoodCount`]. Runs after anything that may
+/// have eaten food or cleared the board.
+pub fn spawn_food(
+    arena: Res<ArenaConfig>,
+    theme: Res<Theme>,
+    mut rng: ResMut<GameRng>,
+    food_count: Res<FoodCount>,
+    mut commands: Commands,
+    // Everything on the board has a position, so this covers snakes, food and
+    // obstacles alike.
+    board: Query<(&Position, Has<Food>)>,
+) {
+    let mut occupied = board
+        .iter()
+        .map(|(position, _)| *position)
+        .collect::<Vec<Position>>();
+    let foods = board.iter().filter(|(_, is_food)| *is_food).count();
+    // Top the board back up, without asking for more cells than are left.
+    // Fresh segments can share a cell, so only distinct cells count.
+    let taken = occupied.iter().collect::<HashSet<_>>().len();
+    let free_cells = ((arena.width * arena.height) as usize).saturating_sub(taken);
+    let missing = food_count.0.saturating_sub(foods).min(free_cells);
+    for _ in 0..missing {
+        let position = arena.random_free_position(&mut rng.rng, &occupied);
+        occupied.push(position);
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: theme.food,
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Food)
+            .insert(position)
+            .insert(Size::square(0.8));
+    }
+}
+
+pub fn spawn_bonus_food(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    mut rng: ResMut<GameRng>,
+    mut foods_since_bonus: ResMut<FoodsSinceBonus>,
+    // Snakes, food and obstacles alike, as in `spawn_food`.
+    board: Query<&Position>,
+    bonus_food: Query<(), With<BonusFood>>,
+) {
+    if foods_since_bonus.0 < BONUS_FOOD_INTERVAL || !bonus_food.is_empty() {
+        return;
+    }
+    let occupied = board.iter().copied().collect::<Vec<Position>>();
+    let taken = occupied.iter().collect::<HashSet<_>>().len();
+    if taken >= (arena.width * arena.height) as usize {
+        return;
+    }
+    foods_since_bonus.0 = 0;
+    let position = arena.random_free_position(&mut rng.rng, &occupied);
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: BONUS_FOOD_COLOR,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(BonusFood(Timer::new(BONUS_FOOD_LIFETIME, TimerMode::Once)))
+        .insert(position)
+        .insert(Size::square(0.95));
+}
+
+pub fn spawn_walls(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    heads: Query<(&SnakeHead, &Position)>,
+) {
+    // Leave each snake's starting lane open so no round ends on the first
+    // stretch.
+    let mut lanes = Vec::new();
+    for (head, &position) in heads.iter() {
+        let mut cell = position;
+        while arena.contains(&cell) {
+            lanes.push(cell);
+            cell = cell.step(head.direction);
+        }
+    }
+    for position in arena.walls.cells(arena.width, arena.height) {
+        if lanes.contains(&position) {
+            continue;
+        }
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: WALL_COLOR,
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Wall)
+            .insert(Obstacle)
+            .insert(position)
+            .insert(Size::square(1.));
+    }
+}
+
+/// Cells in and right around each head, kept clear so no round starts
+/// boxed in.
+fn around_heads(heads: &Query<&Position, With<SnakeHead>>) -> Vec<Position> {
+    heads
+        .iter()
+        .flat_map(|head| {
+            (-1..=1).flat_map(move |dx| {
+                (-1..=1).map(move |dy| Position {
+                    x: head.x + dx,
+                    y: head.y + dy,
+                })
+            })
+        })
+        .collect()
+}
+
+fn free_cell_count(arena: &ArenaConfig, occupied: &[Position]) -> usize {
+    (0..arena.width as i32)
+        .flat_map(|x| (0..arena.height as i32).map(move |y| Position { x, y }))
+        .filter(|position| !occupied.contains(position))
+        .count()
+}
+
+pub fn spawn_obstacles(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    mut rng: ResMut<GameRng>,
+    heads: Query<&Position, With<SnakeHead>>,
+    segments: Query<&Position, With<SnakeSegment>>,
+    walls: Query<&Position, With<Wall>>,
+) {
+    let mut occupied = segments
+        .iter()
+        .chain(walls.iter())
+        .copied()
+        .chain(around_heads(&heads))
+        .collect::<Vec<Position>>();
+    // Walls can leave small arenas with fewer free cells than obstacles.
+    let free_cells = free_cell_count(&arena, &occupied);
+    for _ in 0..(arena.obstacles as usize).min(free_cells) {
+        let position = arena.random_free_position(&mut rng.rng, &occupied);
+        occupied.push(position);
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: OBSTACLE_COLOR,
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Obstacle)
+            .insert(position)
+            .insert(Size::square(0.9));
+    }
+}
+
+/// Puts a linked pair of [`Portal`]s on free cells when the rules ask for
+/// them, keeping clear of the heads like the obstacles do.
+pub fn spawn_portals(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    mut rng: ResMut<GameRng>,
+    heads: Query<&Position, With<SnakeHead>>,
+    board: Query<&Position>,
+) {
+    if !rules.portals {
+        return;
+    }
+    let mut occupied = board
+        .iter()
+        .copied()
+        .chain(around_heads(&heads))
+        .collect::<Vec<Position>>();
+    if free_cell_count(&arena, &occupied) < 2 {
+        return;
+    }
+    let entrance = arena.random_free_position(&mut rng.rng, &occupied);
+    occupied.push(entrance);
+    let exit = arena.random_free_position(&mut rng.rng, &occupied);
+    for (position, exit) in [(entrance, exit), (exit, entrance)] {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: PORTAL_COLOR,
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Portal { exit })
+            .insert(position)
+            .insert(Size::square(0.9));
+    }
+}
+
+pub fn expire_bonus_food(
+    mut commands: Commands,
+    time: Res<Time>,
+    playback: Option<Res<Playback>>,
+    mut recording: ResMut<Recording>,
+    mut bonus_food: Query<(Entity, &mut BonusFood, &mut Size)>,
+) {
+    for (entity, mut bonus, mut size) in bonus_food.iter_mut() {
+        bonus.0.tick(time.delta());
+        // A replay removes the bonus food on the recorded tick instead.
+        if bonus.0.finished() && playback.is_none() {
+            commands.entity(entity).despawn();
+            record_bonus_expiry(&mut recording);
+        } else {
+            // Shrink towards the regular food size as time runs out.
+            *size = Size::square(0.6 + 0.35 * bonus.0.fraction_remaining());
+        }
+    }
+}
+
+pub fn log_seed(rng: Res<GameRng>) {
+    info!("Random seed: {}", rng.seed);
+}
+
+pub fn load_sounds(mut commands: Commands, asset_server: Option<Res<AssetServer>>) {
+    // Headless apps have no asset server and play no sound.
+    let Some(asset_server) = asset_server else {
+        return;
+    };
+    commands.insert_resource(GameSounds {
+        eat: asset_server.load(EAT_SOUND_PATH),
+        turn: asset_server.load(TURN_SOUND_PATH),
+        death: asset_server.load(DEATH_SOUND_PATH),
+    });
+}
+
+pub fn drop_missing_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sounds: Res<GameSounds>,
+) {
+    for handle in [&sounds.eat, &sounds.turn, &sounds.death] {
+        if let Some(LoadState::Failed(err)) = asset_server.get_load_state(handle) {
+            warn!("Playing without sound: {err}");
+            commands.remove_resource::<GameSounds>();
+            return;
+        }
+    }
+}
+
+pub fn toggle_mute(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<SoundSettings>) {
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        settings.muted = !settings.muted;
+    }
+}
+
+fn play_sound(commands: &mut Commands, source: &Handle<AudioSource>, settings: &SoundSettings) {
+    if settings.muted {
+        return;
+    }
+    commands.spawn(AudioBundle {
+        source: source.clone(),
+        settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.volume)),
+    });
+}
+
+pub fn play_eat_sound(
+    mut commands: Commands,
+    sounds: Option<Res<GameSounds>>,
+    settings: Res<SoundSettings>,
+    mut growth_reader: EventReader<GrowthEvent>,
+) {
+    // One sound per frame is plenty, even if both snakes ate.
+    if growth_reader.read().last().is_none() {
+        return;
+    }
+    if let Some(sounds) = sounds {
+        play_sound(&mut commands, &sounds.eat, &settings);
+    }
+}
+
+pub fn play_turn_sound(
+    mut commands: Commands,
+    sounds: Option<Res<GameSounds>>,
+    settings: Res<SoundSettings>,
+    mut turn_reader: EventReader<TurnEvent>,
+) {
+    if turn_reader.read().last().is_none() {
+        return;
+    }
+    if let Some(sounds) = sounds {
+        play_sound(&mut commands, &sounds.turn, &settings);
+    }
+}
+
+pub fn play_death_sound(
+    mut commands: Commands,
+    sounds: Option<Res<GameSounds>>,
+    settings: Res<SoundSettings>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+) {
+    if game_over_reader.read().last().is_none() {
+        return;
+    }
+    if let Some(sounds) = sounds {
+        play_sound(&mut commands, &sounds.death, &settings);
+    }
+}
+
+pub fn setup_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub fn spawn_arena_background(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    images: Option<ResMut<Assets<Image>>>,
+) {
+    // Headless apps have no images to draw into, and nothing to draw on.
+    let texture = images
+        .map(|mut images| images.add(checkerboard(&arena)))
+        .unwrap_or_default();
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                // Sized by `size_scaling`, like the one pixel sprites.
+                custom_size: Some(Vec2::ONE),
+                ..default()
+            },
+            texture,
+            transform: Transform::from_xyz(0., 0., -1.),
+            ..default()
+        })
+        .insert(ArenaBackground)
+        .insert(Size {
+            width: arena.width as f32,
+            height: arena.height as f32,
+        });
+}
+
+/// One pixel per arena cell, alternating between the two arena colors and
+/// drawn without smoothing.
+fn checkerboard(arena: &ArenaConfig) -> Image {
+    let (width, height) = (arena.width, arena.height);
+    // Image rows run top to bottom, arena rows bottom to top.
+    let data = (0..height)
+        .flat_map(|row| (0..width).map(move |x| (x, height - 1 - row)))
+        .flat_map(|(x, y)| {
+            let color = if (x + y) % 2 == 0 {
+                ARENA_COLOR
+            } else {
+                ARENA_ALT_COLOR
+            };
+            color.to_srgba().to_u8_array()
+        })
+        .collect();
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+pub fn spawn_grid_lines(mut commands: Commands, arena: Res<ArenaConfig>) {
+    let vertical = (0..=arena.width).map(|index| GridLine {
+        vertical: true,
+        index,
+    });
+    let horizontal = (0..=arena.height).map(|index| GridLine {
+        vertical: false,
+        index,
+    });
+    for line in vertical.chain(horizontal) {
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: GRID_COLOR,
+                    ..default()
+                },
+                // Above the checkerboard, below everything standing on it.
+                transform: Transform::from_xyz(0., 0., -0.5),
+                ..default()
+            })
+            .insert(line);
+    }
+}
+
+/// Lays the grid lines along the cell boundaries. Like [`size_scaling`], this
+/// runs every frame so the grid follows the window when it is resized.
+pub fn position_grid_lines(
+    arena: Res<ArenaConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut lines: Query<(&GridLine, &mut Transform)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let tile = arena.tile_size(window.size());
+    let arena_size = Vec2::new(arena.width as f32, arena.height as f32) * tile;
+    for (line, mut transform) in lines.iter_mut() {
+        let offset = line.index as f32 * tile;
+        let (translation, scale) = if line.vertical {
+            (
+                Vec2::new(offset - arena_size.x / 2., 0.),
+                Vec2::new(GRID_LINE_WIDTH, arena_size.y),
+            )
+        } else {
+            (
+                Vec2::new(0., offset - arena_size.y / 2.),
+                Vec2::new(arena_size.x, GRID_LINE_WIDTH),
+            )
+        };
+        transform.translation = translation.extend(transform.translation.z);
+        transform.scale = scale.extend(1.);
+    }
+}
+
+pub fn cycle_theme(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<ArenaConfig>,
+    mut theme: ResMut<Theme>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        *theme = theme.next(&arena);
+    }
+}
+
+/// Repaints the background, heads and food in the current theme. Bodies are
+/// left to [`shade_segments`].
+pub fn apply_theme(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    mut heads: Query<(&mut Sprite, &Player), With<SnakeHead>>,
+    mut food: Query<&mut Sprite, (With<Food>, Without<SnakeHead>)>,
+) {
+    commands.insert_resource(ClearColor(theme.background));
+    for (mut sprite, player) in heads.iter_mut() {
+        sprite.color = player.head_color(&theme);
+    }
+    for mut sprite in food.iter_mut() {
+        sprite.color = theme.food;
+    }
+}
+
+pub fn toggle_grid(keyboard_input: Res<ButtonInput<KeyCode>>, mut show_grid: ResMut<ShowGrid>) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        show_grid.0 = !show_grid.0;
+    }
+}
+
+pub fn show_grid(show_grid: Res<ShowGrid>, mut lines: Query<&mut Visibility, With<GridLine>>) {
+    for mut visibility in lines.iter_mut() {
+        *visibility = if show_grid.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+pub fn settings_hint(rules: &GameRules, mode: GameMode, level: DifficultyLevel) -> String {
+    let walls = match rules.arena_mode {
+        ArenaMode::Walled => "on",
+        ArenaMode::Wrapping => "off, wrap around",
+    };
+    let portals = if rules.portals { "on" } else { "off" };
+    let players = match mode {
+        GameMode::Single => "1",
+        GameMode::Versus => "2 (arrows vs WASD/IJKL)",
+    };
+    let levels = DifficultyLevel::ALL
+        .iter()
+        .map(|option| {
+            if *option == level {
+                format!("[{}]", option.name())
+            } else {
+                option.name().to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ");
+    format!(
+        "\n\nDifficulty: {levels} (Up/Down or click below)\nWalls: {walls} (T to toggle)\nPortals: {portals} (P to toggle)\nPlayers: {players} (V to toggle)"
+    )
+}
+
+pub fn spawn_screen_text(
+    commands: &mut Commands,
+    text: &str,
+    rules: &GameRules,
+    mode: GameMode,
+    level: DifficultyLevel,
+    state: GameState,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(StateScoped(state))
+        .with_children(|parent| {
+            parent
+                .spawn(
+                    TextBundle::from_sections([
+                        TextSection::new(
+                            text,
+                            TextStyle {
+                                font_size: 36.,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        TextSection::new(
+                            settings_hint(rules, mode, level),
+                            TextStyle {
+                                font_size: 20.,
+                                color: Color::srgb(0.6, 0.6, 0.6),
+                                ..default()
+                            },
+                        ),
+                    ])
+                    .with_text_justify(JustifyText::Center),
+                )
+                .insert(SettingsText);
+        });
+}
+
+pub fn spawn_menu_screen(
+    mut commands: Commands,
+    high_score: Res<HighScore>,
+    rules: Res<GameRules>,
+    mode: Res<GameMode>,
+    level: Res<DifficultyLevel>,
+) {
+    spawn_screen_text(
+        &mut commands,
+        &format!(
+            "Snake\n\nBest: {}\n\nPress Enter or Space to play",
+            high_score.0
+        ),
+        &rules,
+        *mode,
+        *level,
+        GameState::Menu,
+    );
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(40.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(12.),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(StateScoped(GameState::Menu))
+        .with_children(|row| {
+            for option in DifficultyLevel::ALL {
+                row.spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(100.),
+                        height: Val::Px(40.),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: if option == *level {
+                        BUTTON_SELECTED_COLOR
+                    } else {
+                        BUTTON_COLOR
+                    }
+                    .into(),
+                    ..default()
+                })
+                .insert(DifficultyButton(option))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        option.name(),
+                        TextStyle {
+                            font_size: 20.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                });
+            }
+        });
+}
+
+pub fn spawn_game_over_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    high_score: Res<HighScore>,
+    rules: Res<GameRules>,
+    mode: Res<GameMode>,
+    level: Res<DifficultyLevel>,
+    heads: Query<(&Player, Has<Crashed>)>,
+) {
+    let result = match *mode {
+        GameMode::Single => format!("Score: {}\nBest: {}", score.0, high_score.0),
+        GameMode::Versus => {
+            let survivors = heads
+                .iter()
+                .filter(|(_, crashed)| !crashed)
+                .map(|(player, _)| *player)
+                .collect::<Vec<Player>>();
+            match survivors.as_slice() {
+                [winner] => format!("Player {} wins!", winner.number()),
+                _ => "Draw!".to_string(),
+            }
+        }
+    };
+    spawn_screen_text(
+        &mut commands,
+        &format!("Game over\n\n{result}\n\nPress Space to play again, Esc for the menu"),
+        &rules,
+        *mode,
+        *level,
+        GameState::GameOver,
+    );
+}
+
+pub fn spawn_win_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    high_score: Res<HighScore>,
+    rules: Res<GameRules>,
+    mode: Res<GameMode>,
+    level: Res<DifficultyLevel>,
+) {
+    let result = match *mode {
+        GameMode::Single => format!("Score: {}\nBest: {}", score.0, high_score.0),
+        GameMode::Versus => "The board is full".to_string(),
+    };
+    spawn_screen_text(
+        &mut commands,
+        &format!("You win!\n\n{result}\n\nPress Space to play again, Esc for the menu"),
+        &rules,
+        *mode,
+        *level,
+        GameState::Won,
+    );
+}
+
+pub fn start_game(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Space, KeyCode::Enter]) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn return_to_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+/// Moves the [`DifficultyLevel`] selection and applies the new preset's speed
+/// and arena mode straight away, so T can still override the walls after.
+pub fn select_difficulty(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    arena: Res<ArenaConfig>,
+    mut level: ResMut<DifficultyLevel>,
+    mut difficulty: ResMut<Difficulty>,
+    mut rules: ResMut<GameRules>,
+) {
+    let steps = if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        -1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        1
+    } else {
+        return;
+    };
+    *level = level.offset(steps);
+    apply_difficulty(*level, &arena, &mut difficulty, &mut rules);
+}
+
+fn apply_difficulty(
+    level: DifficultyLevel,
+    arena: &ArenaConfig,
+    difficulty: &mut Difficulty,
+    rules: &mut GameRules,
+) {
+    difficulty.initial_period = Duration::from_secs_f32(arena.tick_secs * level.period_scale());
+    difficulty.step = level.period_step();
+    rules.arena_mode = level.arena_mode();
+}
+
+/// Clicking a difficulty button picks that preset and starts playing.
+pub fn press_difficulty_button(
+    buttons: Query<(&Interaction, &DifficultyButton), Changed<Interaction>>,
+    arena: Res<ArenaConfig>,
+    mut level: ResMut<DifficultyLevel>,
+    mut difficulty: ResMut<Difficulty>,
+    mut rules: ResMut<GameRules>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            *level = button.0;
+            apply_difficulty(*level, &arena, &mut difficulty, &mut rules);
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+/// Lights up the button under the cursor and the one picked.
+pub fn color_difficulty_buttons(
+    level: Res<DifficultyLevel>,
+    mut buttons: Query<(&Interaction, &DifficultyButton, &mut BackgroundColor)>,
+) {
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        background.0 = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_COLOR,
+            Interaction::Hovered => BUTTON_HOVERED_COLOR,
+            Interaction::None if button.0 == *level => BUTTON_SELECTED_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        };
+    }
+}
+
+pub fn toggle_arena_mode(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<GameRules>) {
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        rules.arena_mode = match rules.arena_mode {
+            ArenaMode::Walled => ArenaMode::Wrapping,
+            ArenaMode::Wrapping => ArenaMode::Walled,
+        };
+    }
+}
+
+pub fn toggle_portals(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<GameRules>) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        rules.portals = !rules.portals;
+    }
+}
+
+pub fn toggle_game_mode(keyboard_input: Res<ButtonInput<KeyCode>>, mut mode: ResMut<GameMode>) {
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        *mode = match *mode {
+            GameMode::Single => GameMode::Versus,
+            GameMode::Versus => GameMode::Single,
+        };
+    }
+}
+
+pub fn update_settings_text(
+    rules: Res<GameRules>,
+    mode: Res<GameMode>,
+    level: Res<DifficultyLevel>,
+    mut texts: Query<&mut Text, With<SettingsText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.sections[1].value = settings_hint(&rules, *mode, *level);
+    }
+}
+
+pub fn save_high_score(score: Res<Score>, mut high_score: ResMut<HighScore>) {
+    if score.0 <= high_score.0 {
+        return;
+    }
+    high_score.0 = score.0;
+    if let Some(path) = highscore::path() {
+        if let Err(err) = highscore::save(&path, score.0) {
+            warn!("Could not save high score to {}: {err}", path.display());
+        }
+    }
+}
+
+pub fn reset_score(mut score: ResMut<Score>, mut foods_since_bonus: ResMut<FoodsSinceBonus>) {
+    score.0 = 0;
+    foods_since_bonus.0 = 0;
+}
+
+pub fn emit_spawn_signal(mut growth_writer: EventWriter<SpawnEvent>) {
+    growth_writer.send(SpawnEvent);
+}
+
+/// Winds the countdown back to its full length and holds the snakes until it
+/// runs out. During a countdown the state doesn't change, so resetting the
+/// timer is all it takes.
+pub fn restart_countdown(
+    mut countdown: ResMut<StartCountdown>,
+    mut next_phase: ResMut<NextState<RoundPhase>>,
+) {
+    countdown.0.reset();
+    next_phase.set(RoundPhase::Countdown);
+}
+
+pub fn spawn_countdown_text(mut commands: Commands, texts: Query<Entity, With<CountdownText>>) {
+    // A restart while "Go!" is still up replaces it.
+    for entity in texts.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.spawn((
+        countdown_text_bundle(""),
+        CountdownText(Timer::new(GO_DISPLAY, TimerMode::Once)),
+        StateScoped(RoundPhase::Countdown),
+    ));
+}
+
+/// "Go!" in place of the countdown, until its own timer runs out.
+pub fn spawn_go_text(mut commands: Commands) {
+    commands.spawn((
+        countdown_text_bundle("Go!"),
+        CountdownText(Timer::new(GO_DISPLAY, TimerMode::Once)),
+        StateScoped(GameState::Playing),
+    ));
+}
+
+fn countdown_text_bundle(value: &str) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size: 96.,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+    .with_text_justify(JustifyText::Center)
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        width: Val::Percent(100.),
+        top: Val::Percent(40.),
+        ..default()
+    })
+}
+
+pub fn tick_countdown(time: Res<Time>, mut countdown: ResMut<StartCountdown>) {
+    countdown.0.tick(time.delta());
+}
+
+pub fn finish_countdown(
+    countdown: Res<StartCountdown>,
+    mut next_phase: ResMut<NextState<RoundPhase>>,
+) {
+    if countdown.0.finished() {
+        next_phase.set(RoundPhase::Running);
+    }
+}
+
+pub fn update_countdown_text(
+    countdown: Res<StartCountdown>,
+    mut texts: Query<&mut Text, With<CountdownText>>,
+) {
+    let seconds = countdown.0.remaining_secs().ceil();
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("{seconds}");
+    }
+}
+
+pub fn fade_go_text(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut texts: Query<(Entity, &mut CountdownText)>,
+) {
+    for (entity, mut go) in texts.iter_mut() {
+        if go.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn spawn_snake(
+    mut spawn_reader: EventReader<SpawnEvent>,
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    theme: Res<Theme>,
+    mode: Res<GameMode>,
+) {
+    if spawn_reader.read().next().is_none() {
+        return;
+    }
+    let players: &[Player] = match *mode {
+        GameMode::Single => &[Player::One],
+        GameMode::Versus => &[Player::One, Player::Two],
+    };
+    for &player in players {
+        spawn_player(&mut commands, &arena, &theme, player);
+    }
+}
+
+/// Spawns `player`'s two segment snake on its starting cells.
+fn spawn_player(commands: &mut Commands, arena: &ArenaConfig, theme: &Theme, player: Player) {
+    let (position, direction) = player.start(arena);
+    let head = commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: player.head_color(theme),
+                ..default()
+            },
+            transform: Transform {
+                scale: Vec3::new(10., 10., 10.),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(SnakeHead { direction })
+        .insert(SnakeSegment)
+        .insert(player)
+        .insert(position)
+        .insert(PreviousPosition(position))
+        // Longer than wide, so the head shows which way it faces.
+        .insert(Size {
+            width: 0.8,
+            height: 0.95,
+        })
+        .insert(LastTailPosition::default())
+        .insert(Steering::default())
+        .with_children(|head| {
+            // Eyes near the front edge, in the head's unit square.
+            for x in [-0.22, 0.22] {
+                head.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: SNAKE_EYE_COLOR,
+                        ..default()
+                    },
+                    transform: Transform {
+                        translation: Vec3::new(x, 0.25, 0.1),
+                        scale: Vec3::new(0.2, 0.15, 1.),
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+        })
+        .id();
+    let tail = spawn_segment(
+        commands,
+        position.step(direction.opposite()),
+        player.segment_color(theme),
+    );
+    commands
+        .entity(head)
+        .insert(SnakeSegments(vec![head, tail]));
+}
+
+pub fn size_scaling(
+    arena: Res<ArenaConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut query: Query<(&Size, &mut Transform)>,
+) {
+    // Nothing to scale against when running headless.
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let tile = arena.tile_size(window.size());
+    for (sprite_size, mut transform) in query.iter_mut() {
+        transform.scale = Vec3::new(sprite_size.width * tile, sprite_size.height * tile, 1.0);
+    }
+}
+
+pub fn position_translation(
+    arena: Res<ArenaConfig>,
+    move_timer: Res<MoveTimer>,
+    windows: Query<&Window>,
+    mut query: Query<(&Position, Option<&PreviousPosition>, &mut Transform)>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let center = |position: &Position| arena.cell_center(position, window.size());
+    // Taken from the timer rather than counted in frames, so the glide keeps
+    // pace with speed changes and never overshoots the current cell.
+    let progress = move_timer.0.fraction();
+    for (position, previous, mut transform) in query.iter_mut() {
+        let current = center(position);
+        // A segment that wrapped around the arena jumped across the board, and
+        // gliding would streak it over every cell in between.
+        let from = match previous {
+            Some(previous)
+                if (previous.0.x - position.x).abs() + (previous.0.y - position.y).abs() <= 1 =>
+            {
+                center(&previous.0)
+            }
+            _ => current,
+        };
+        transform.translation = from.lerp(current, progress).extend(transform.translation.z);
+    }
+}
+
+/// Shades each body from close to its head color behind the head down to a
+/// darker shade of its segment color at the tail. Only snakes whose segment
+/// list changed, from spawning or growing, are recolored, unless the theme
+/// changed too.
+pub fn shade_segments(
+    theme: Res<Theme>,
+    snakes: Query<(Ref<SnakeSegments>, &Player)>,
+    mut sprites: Query<&mut Sprite>,
+) {
+    for (segments, player) in snakes.iter() {
+        if !segments.is_changed() && !theme.is_changed() {
+            continue;
+        }
+        let front = player.head_color(&theme);
+        let back = player.segment_color(&theme).darker(0.15);
+        let body = segments.0.len().saturating_sub(1).max(1) as f32;
+        for (i, segment) in segments.0.iter().enumerate().skip(1) {
+            if let Ok(mut sprite) = sprites.get_mut(*segment) {
+                sprite.color = front.mix(&back, i as f32 / body);
+            }
+        }
+    }
+}
+
+/// Turns each head to face its direction. Only touches the rotation, which
+/// [`position_translation`] and [`size_scaling`] leave alone.
+pub fn rotate_heads(mut heads: Query<(&SnakeHead, &mut Transform)>) {
+    for (head, mut transform) in heads.iter_mut() {
+        transform.rotation = Quat::from_rotation_z(head.direction.angle());
+    }
+}
+
+/// Direction the left stick points in, if it is pushed past `deadzone`.
+pub fn stick_direction(x: f32, y: f32, deadzone: f32) -> Option<Direction> {
+    if x.abs().max(y.abs()) < deadzone {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x > 0. {
+            Direction::Right
+        } else {
+            Direction::Left
+        })
+    } else {
+        Some(if y > 0. {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
+
+pub fn snake_movement_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    deadzone: Res<StickDeadzone>,
+    mode: Res<GameMode>,
+    mut heads: Query<(&mut SnakeHead, &mut Steering, &Player)>,
+) {
+    for (mut head, mut steering, player) in heads.iter_mut() {
+        let keys = player.keys(*mode);
+        let held_key = keys
+            .iter()
+            .find(|(key, _)| keyboard_input.pressed(*key))
+            .map(|(_, direction)| *direction);
+        let key_pressed = keys
+            .iter()
+            .any(|(key, _)| keyboard_input.just_pressed(*key));
+
+        let mut held_button = None;
+        let mut button_pressed = false;
+        let mut stick = None;
+        // Looked up every frame, so gamepads plugged in or pulled out mid-round
+        // simply join or drop out.
+        for gamepad in player.gamepads(*mode, &gamepads) {
+            for (button_type, direction) in DPAD_BUTTONS {
+                let button = GamepadButton::new(gamepad, button_type);
+                if gamepad_buttons.pressed(button) {
+                    held_button.get_or_insert(direction);
+                }
+                button_pressed |= gamepad_buttons.just_pressed(button);
+            }
+            let axis = |axis_type| {
+                gamepad_axes
+                    .get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or(0.)
+            };
+            stick = stick.or(stick_direction(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+                deadzone.0,
+            ));
+        }
+        let stick_pushed = stick.is_some() && stick != steering.stick;
+        steering.stick = stick;
+
+        if key_pressed {
+            steering.device = InputDevice::Keyboard;
+        } else if button_pressed || stick_pushed {
+            steering.device = InputDevice::Gamepad;
+        }
+        let held_gamepad = held_button.or(stick);
+        let direction = match steering.device {
+            InputDevice::Keyboard => held_key.or(held_gamepad),
+            InputDevice::Gamepad => held_gamepad.or(held_key),
+        }
+        .unwrap_or(head.direction);
+        if direction != head.direction.opposite() {
+            head.direction = direction;
+        }
+    }
+}
+
+pub fn reset_move_timer(difficulty: Res<Difficulty>, mut move_timer: ResMut<MoveTimer>) {
+    move_timer.0 = Timer::new(difficulty.period(0), TimerMode::Repeating);
+}
+
+/// Clears the tick the move timer may have just finished, so nothing moves on
+/// the first frame of a countdown.
+pub fn rewind_move_timer(mut move_timer: ResMut<MoveTimer>) {
+    move_timer.0.reset();
+}
+
+pub fn update_move_period(
+    difficulty: Res<Difficulty>,
+    snakes: Query<&SnakeSegments>,
+    mut move_timer: ResMut<MoveTimer>,
+) {
+    let longest = snakes.iter().map(|segments| segments.0.len()).max();
+    let foods_eaten = longest.unwrap_or(0).saturating_sub(INITIAL_SNAKE_LENGTH) as u32;
+    move_timer.0.set_duration(difficulty.period(foods_eaten));
+}
+
+pub fn tick_move_timer(time: Res<Time>, mut move_timer: ResMut<MoveTimer>) {
+    move_timer.0.tick(time.delta());
+}
+
+pub fn restart_requested(keyboard_input: Res<ButtonInput<KeyCode>>) -> bool {
+    keyboard_input.just_pressed(KeyCode::KeyR)
+}
+
+/// Whether every snake on the board has all of its segments, which isn't yet
+/// the case on the frame a round starts or a snake respawns.
+pub fn snakes_spawned(
+    snakes: Query<&SnakeSegments>,
+    segments: Query<(), With<SnakeSegment>>,
+) -> bool {
+    !snakes.is_empty()
+        && snakes.iter().all(|snake| {
+            !snake.0.is_empty() && snake.0.iter().all(|segment| segments.contains(*segment))
+        })
+}
+
+pub fn move_timer_finished(move_timer: Res<MoveTimer>) -> bool {
+    move_timer.0.just_finished()
+}
+
+// One query for everything on the board that isn't a snake keeps this
+// under the argument limit.
+#[allow(clippy::type_complexity)]
+pub fn snake_movement(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    mut heads: Query<(Entity, &SnakeHead, &SnakeSegments, &mut LastTailPosition)>,
+    mut positions: Query<(&mut Position, &mut PreviousPosition)>,
+    // Obstacles, food and portals; only snake segments have a previous
+    // position.
+    board: Query<(&Position, Has<Obstacle>, Option<&Portal>), Without<PreviousPosition>>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+    let food = board
+        .iter()
+        .filter(|(_, is_obstacle, portal)| !is_obstacle && portal.is_none())
+        .map(|(position, ..)| *position)
+        .collect::<Vec<Position>>();
+    let portals = board
+        .iter()
+        .filter_map(|(position, .., portal)| portal.map(|portal| (position, portal)))
+        .collect::<Vec<_>>();
+    // All snakes move at once, so collisions are checked against where
+    // every snake was before this tick and where the other heads end up.
+    // A snake missing any of its segments sits the tick out.
+    let moves = heads
+        .iter()
+        .filter_map(|(head_entity, head, segments, _)| {
+            let segment_positions = segments
+                .0
+                .iter()
+                .map(|e| positions.get(*e).ok().map(|(position, _)| *position))
+                .collect::<Option<Vec<Position>>>()?;
+            let next = rules.advance_through(
+                &arena,
+                portals.iter().copied(),
+                *segment_positions.first()?,
+                head.direction,
+            );
+            Some((head_entity, segment_positions, next))
+        })
+        .collect::<Vec<_>>();
+    let solid = solid_cells(
+        moves
+            .iter()
+            .map(|(_, segment_positions, next)| (segment_positions.as_slice(), next)),
+        &food,
+    );
+    let mut crashed_any = false;
+    for (head_entity, _, next) in &moves {
+        let crashed = !arena.contains(next)
+            || board
+                .iter()
+                .any(|(position, is_obstacle, ..)| is_obstacle && position == next)
+            || moves
+                .iter()
+                .zip(&solid)
+                .any(|((other, _, other_next), other_solid)| {
+                    other_solid.contains(next) || (other != head_entity && other_next == next)
+                });
+        if crashed {
+            commands.entity(*head_entity).insert(Crashed);
+            crashed_any = true;
+        }
+    }
+    if crashed_any {
+        game_over_writer.send(GameOverEvent);
+    }
+    for (head_entity, segment_positions, next) in moves {
+        let Ok((_, _, segments, mut last_tail_position)) = heads.get_mut(head_entity) else {
+            continue;
+        };
+        *last_tail_position = LastTailPosition(segment_positions.last().copied());
+        // The head moves on and every other segment takes the cell in front.
+        let targets = std::iter::once(next).chain(segment_positions.iter().copied());
+        for ((segment, position), target) in segments.0.iter().zip(&segment_positions).zip(targets)
+        {
+            if let Ok((mut current, mut previous)) = positions.get_mut(*segment) {
+                previous.0 = *position;
+                *current = target;
+            }
+        }
+    }
+}
+
+/// Cells of each snake that block heads on the coming tick, given its cells
+/// from head to tail and the cell its head moves into. Tails move out of the
+/// way on the same tick, unless their snake eats and grows back into the cell.
+pub fn solid_cells<'a>(
+    snakes: impl IntoIterator<Item = (&'a [Position], &'a Position)>,
+    food: &[Position],
+) -> Vec<&'a [Position]> {
+    snakes
+        .into_iter()
+        .map(|(cells, next)| {
+            let grows = food.contains(next);
+            let len = cells.len().saturating_sub(usize::from(!grows));
+            &cells[..len.max(1).min(cells.len())]
+        })
+        .collect()
+}
+
+/// Compares each head's direction with the way it moved on the last tick,
+/// which its first two segments still show.
+pub fn send_turn_events(
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    heads: Query<(&SnakeHead, &SnakeSegments)>,
+    positions: Query<&Position>,
+    mut turn_writer: EventWriter<TurnEvent>,
+) {
+    for (head, segments) in heads.iter() {
+        let mut cells = segments
+            .0
+            .iter()
+            .filter_map(|segment| positions.get(*segment).ok());
+        let (Some(head_position), Some(neck)) = (cells.next(), cells.next()) else {
+            continue;
+        };
+        let moved = rules.direction_between(&arena, *neck, *head_position);
+        if moved.is_some_and(|moved| moved != head.direction) {
+            turn_writer.send(TurnEvent);
+        }
+    }
+}
+
+pub fn update_move_candidates(
+    arena: Res<ArenaConfig>,
+    rules: Res<GameRules>,
+    heads: Query<(Entity, &SnakeHead, &Position)>,
+    portals: Query<(&Position, &Portal)>,
+    mut move_candidates: ResMut<MoveCandidates>,
+) {
+    move_candidates.0 = heads
+        .iter()
+        .map(|(entity, head, position)| {
+            let next = rules.advance_through(&arena, &portals, *position, head.direction);
+            (entity, next)
+        })
+        .collect();
+}
+
+pub fn spawn_segment(commands: &mut Commands, position: Position, color: Color) -> Entity {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite { color, ..default() },
+            ..default()
+        })
+        .insert(SnakeSegment)
+        .insert(position)
+        .insert(PreviousPosition(position))
+        .insert(Size::square(0.65))
+        .id()
+}
+
+pub fn snake_eating(
+    mut commands: Commands,
+    mut growth_writer: EventWriter<GrowthEvent>,
+    mut score: ResMut<Score>,
+    mut foods_since_bonus: ResMut<FoodsSinceBonus>,
+    food_positions: Query<(Entity, &Position), With<Food>>,
+    bonus_food_positions: Query<(Entity, &Position), With<BonusFood>>,
+    head_positions: Query<(Entity, &Position), With<SnakeHead>>,
+) {
+    head_positions
+        .iter()
+        .for_each(|(head_entity, head_position)| {
+            food_positions.iter().for_each(|(entity, food_position)| {
+                if food_position == head_position {
+                    commands.entity(entity).despawn();
+                    growth_writer.send(GrowthEvent {
+                        snake: head_entity,
+                        segments: FOOD_SEGMENTS,
+                    });
+                    score.0 += FOOD_POINTS;
+                    foods_since_bonus.0 += 1;
+                }
+            });
+            bonus_food_positions
+                .iter()
+                .for_each(|(entity, bonus_food_position)| {
+                    if bonus_food_position == head_position {
+                        commands.entity(entity).despawn();
+                        growth_writer.send(GrowthEvent {
+                            snake: head_entity,
+                            segments: BONUS_FOOD_SEGMENTS,
+                        });
+                        score.0 += BONUS_FOOD_POINTS;
+                    }
+                });
+        });
+}
+
+/// Adds the segments from every growth event. The first new segment takes the
+/// cell the tail just left, and any further ones carry on in the same line
+/// behind it, piling up on the last cell once the arena edge is reached.
+pub fn snake_growth(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    theme: Res<Theme>,
+    rules: Res<GameRules>,
+    mut heads: Query<(&mut SnakeSegments, &LastTailPosition, &Player)>,
+    positions: Query<&Position>,
+    mut growth_reader: EventReader<GrowthEvent>,
+) {
+    let mut growths: Vec<(Entity, u32)> = Vec::new();
+    for growth in growth_reader.read() {
+        match growths.iter_mut().find(|(snake, _)| *snake == growth.snake) {
+            Some((_, segments)) => *segments += growth.segments,
+            None => growths.push((growth.snake, growth.segments)),
+        }
+    }
+    for (snake, count) in growths {
+        let Ok((mut segments, last_tail_position, player)) = heads.get_mut(snake) else {
+            continue;
+        };
+        let Some(mut position) = last_tail_position.0 else {
+            continue;
+        };
+        let behind = segments
+            .0
+            .last()
+            .and_then(|tail| positions.get(*tail).ok())
+            .and_then(|tail| rules.direction_between(&arena, *tail, position));
+        for i in 0..count {
+            if i > 0 {
+                if let Some(next) = behind
+                    .map(|direction| rules.advance(&arena, position, direction))
+                    .filter(|next| arena.contains(next))
+                {
+                    position = next;
+                }
+            }
+            segments.0.push(spawn_segment(
+                &mut commands,
+                position,
+                player.segment_color(&theme),
+            ));
+        }
+    }
+}
+
+/// Ends the round once there is neither food left to eat nor a free cell to
+/// put one on.
+pub fn check_board_full(
+    arena: Res<ArenaConfig>,
+    board: Query<(&Position, Has<Food>)>,
+    mut win_writer: EventWriter<WinEvent>,
+) {
+    if board.iter().any(|(_, is_food)| is_food) {
+        return;
+    }
+    let taken = board
+        .iter()
+        .map(|(position, _)| position)
+        .collect::<HashSet<_>>()
+        .len();
+    if taken >= (arena.width * arena.height) as usize {
+        win_writer.send(WinEvent);
+    }
+}
+
+pub fn game_over(
+    mode: Res<GameMode>,
+    lives: Res<Lives>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut win_reader: EventReader<WinEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // A lone snake with lives to spare respawns instead, see [`lose_life`].
+    let out_of_lives = *mode == GameMode::Versus || lives.0 == 0;
+    // A crash on the same tick outweighs filling the board.
+    if game_over_reader.read().next().is_some() && out_of_lives {
+        next_state.set(GameState::GameOver);
+    } else if win_reader.read().next().is_some() {
+        next_state.set(GameState::Won);
+    }
+}
+
+pub fn reset_lives(starting_lives: Res<StartingLives>, mut lives: ResMut<Lives>) {
+    lives.0 = starting_lives.0;
+}
+
+/// Takes a life when the lone snake crashes. While any are left it asks for
+/// a respawn and holds the round for [`RESPAWN_DELAY`] of countdown.
+pub fn lose_life(
+    mut lives: ResMut<Lives>,
+    mut countdown: ResMut<StartCountdown>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut respawn_writer: EventWriter<RespawnEvent>,
+    mut next_phase: ResMut<NextState<RoundPhase>>,
+) {
+    if game_over_reader.read().next().is_none() {
+        return;
+    }
+    lives.0 = lives.0.saturating_sub(1);
+    if lives.0 == 0 {
+        return;
+    }
+    respawn_writer.send(RespawnEvent);
+    countdown.0.reset();
+    let duration = countdown.0.duration();
+    countdown
+        .0
+        .set_elapsed(duration.saturating_sub(RESPAWN_DELAY));
+    next_phase.set(RoundPhase::Countdown);
+}
+
+/// Replaces the crashed snake with a fresh one on the starting cells. Food
+/// and score stay, except for food on those cells, which moves elsewhere.
+pub fn respawn_snake(
+    mut commands: Commands,
+    arena: Res<ArenaConfig>,
+    theme: Res<Theme>,
+    mut spawn_writer: EventWriter<SpawnEvent>,
+    segments: Query<Entity, With<SnakeSegment>>,
+    food: Query<(Entity, &Position), With<Food>>,
+    bonus_food: Query<(Entity, &Position), With<BonusFood>>,
+) {
+    for entity in segments.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (head, direction) = Player::One.start(&arena);
+    let start = [head, head.step(direction.opposite())];
+    for (entity, position) in food.iter().chain(bonus_food.iter()) {
+        if start.contains(position) {
+            commands.entity(entity).despawn();
+        }
+    }
+    spawn_player(&mut commands, &arena, &theme, Player::One);
+    // Has spawn_food put back any food that was moved out of the way.
+    spawn_writer.send(SpawnEvent);
+}
+
+pub fn spawn_lives_text(mut commands: Commands, lives: Res<Lives>) {
+    commands.spawn((
+        TextBundle::from_section(
+            lives_label(lives.0),
+            TextStyle {
+                font_size: 20.,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.),
+            right: Val::Px(8.),
+            ..default()
+        }),
+        LivesText,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+pub fn update_lives_text(lives: Res<Lives>, mut texts: Query<&mut Text, With<LivesText>>) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = lives_label(lives.0);
+    }
+}
+
+fn lives_label(lives: u32) -> String {
+    format!("Lives: {lives}")
+}
+
+pub fn despawn_board(
+    mut commands: Commands,
+    food: Query<Entity, With<Food>>,
+    bonus_food: Query<Entity, With<BonusFood>>,
+    obstacles: Query<Entity, With<Obstacle>>,
+    portals: Query<Entity, With<Portal>>,
+    segments: Query<Entity, With<SnakeSegment>>,
+) {
+    for entity in food
+        .iter()
+        .chain(bonus_food.iter())
+        .chain(obstacles.iter())
+        .chain(portals.iter())
+        .chain(segments.iter())
+    {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkerboard_puts_the_bottom_row_last() {
+        // An even height, so the top and bottom rows start on different colors.
+        let arena = ArenaConfig {
+            width: 5,
+            height: 4,
+            ..default()
+        };
+        let image = checkerboard(&arena);
+        let pixel = |x: u32, y: u32| {
+            let start = ((y * arena.width + x) * 4) as usize;
+            image.data[start..start + 4].to_vec()
+        };
+        let bytes = |color: Color| color.to_srgba().to_u8_array().to_vec();
+        // Image pixel (0, 0) is the top left cell, (0, 3) in the arena.
+        assert_eq!(pixel(0, 0), bytes(ARENA_ALT_COLOR));
+        assert_eq!(pixel(1, 0), bytes(ARENA_COLOR));
+        assert_eq!(pixel(0, arena.height - 1), bytes(ARENA_COLOR));
+    }
+}
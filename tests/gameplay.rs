@@ -0,0 +1,1512 @@
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+use bevy::{
+    input::{
+        gamepad::{
+            GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection,
+            GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+        },
+        keyboard::{Key, KeyboardInput},
+        ButtonState, InputPlugin,
+    },
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+    window::PrimaryWindow,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use snake::{
+    bot::Bot,
+    components::{
+        ArenaBackground, BonusFood, CountdownText, DifficultyButton, Direction, Food, GridLine,
+        Obstacle, Player, Portal, Position, PreviousPosition, SnakeHead, SnakeSegment,
+        SnakeSegments, Wall,
+    },
+    config::{ArenaConfig, WallPattern, MIN_ARENA_SIZE},
+    events::{GameOverEvent, GrowthEvent, SpawnEvent},
+    highscore,
+    replay::{self, Playback, Recording},
+    resources::{
+        ArenaMode, Difficulty, DifficultyLevel, FoodCount, FoodsSinceBonus, GameMode, GameRng,
+        GameRules, GameSounds, HighScore, Lives, MoveTimer, Score, SoundSettings, StartCountdown,
+        StartingLives, StickDeadzone, Theme,
+    },
+    settings::{self, Settings},
+    stats::{format_time, RoundStats, RoundTimerText, SessionBest},
+    systems::{spawn_bonus_food, spawn_food},
+    GameState, RoundPhase, SnakePlugin, BONUS_FOOD_INTERVAL, BONUS_FOOD_POINTS,
+    BONUS_FOOD_SEGMENTS, BUTTON_HOVERED_COLOR, BUTTON_SELECTED_COLOR, FOODS_PER_SPEED_STEP,
+    MIN_MOVE_PERIOD,
+};
+
+/// Headless app on the menu, where every update advances time by more than a
+/// movement tick.
+fn headless_app() -> App {
+    // Keep rounds that end in a test from touching the real high score and replay.
+    std::env::set_var(
+        highscore::PATH_ENV_VAR,
+        std::env::temp_dir().join("snake-test-high-score.txt"),
+    );
+    std::env::set_var(
+        replay::PATH_ENV_VAR,
+        std::env::temp_dir().join("snake-test-replay.toml"),
+    );
+    std::env::set_var(
+        settings::PATH_ENV_VAR,
+        std::env::temp_dir().join("snake-test-settings.toml"),
+    );
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, InputPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            200,
+        )))
+        .insert_resource(ArenaConfig {
+            obstacles: 0,
+            ..default()
+        })
+        .insert_resource(HighScore(0))
+        .insert_resource(GameRng::new(0))
+        // Rounds start moving straight away unless a test wants the countdown.
+        .insert_resource(StartCountdown(Timer::new(Duration::ZERO, TimerMode::Once)))
+        .add_plugins(SnakePlugin);
+    app
+}
+
+fn start_round(app: &mut App) {
+    app.world_mut()
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Playing);
+    app.update();
+}
+
+fn playing_app() -> App {
+    let mut app = headless_app();
+    start_round(&mut app);
+    app
+}
+
+fn food_positions(app: &mut App) -> Vec<Position> {
+    app.world_mut()
+        .query_filtered::<&Position, With<Food>>()
+        .iter(app.world())
+        .copied()
+        .collect()
+}
+
+fn assert_distinct(positions: &[Position]) {
+    for (i, position) in positions.iter().enumerate() {
+        assert!(
+            !positions[i + 1..].contains(position),
+            "two foods on {position:?}"
+        );
+    }
+}
+
+fn head_position(app: &mut App) -> Position {
+    *app.world_mut()
+        .query_filtered::<&Position, With<SnakeHead>>()
+        .single(app.world())
+}
+
+fn set_head_position(app: &mut App, position: Position) {
+    *app.world_mut()
+        .query_filtered::<&mut Position, With<SnakeHead>>()
+        .single_mut(app.world_mut()) = position;
+}
+
+/// Steps until the head moves, so tests don't depend on the first tick.
+fn step(app: &mut App) {
+    let before = head_position(app);
+    for _ in 0..10 {
+        app.update();
+        if head_position(app) != before {
+            return;
+        }
+    }
+    panic!("snake never moved from {before:?}");
+}
+
+#[test]
+fn movement_advances_the_head() {
+    let mut app = playing_app();
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 3 });
+    step(&mut app);
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 4 });
+    step(&mut app);
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 5 });
+}
+
+#[test]
+fn eating_grows_the_segments() {
+    let mut app = playing_app();
+    // Swap the randomly placed food for one right in front of the head.
+    let food = app
+        .world_mut()
+        .query_filtered::<Entity, With<Food>>()
+        .single(app.world());
+    app.world_mut().despawn(food);
+    let food_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((Food, food_cell));
+    step(&mut app);
+    let segments = app
+        .world_mut()
+        .query::<&SnakeSegments>()
+        .single(app.world())
+        .0
+        .len();
+    assert_eq!(segments, 3);
+}
+
+#[test]
+fn bonus_food_grows_several_segments_in_a_line() {
+    let mut app = playing_app();
+    let bonus_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((
+        BonusFood(Timer::new(Duration::from_secs(60), TimerMode::Once)),
+        bonus_cell,
+    ));
+    step(&mut app);
+    let mut segments = app
+        .world_mut()
+        .query_filtered::<&Position, With<SnakeSegment>>()
+        .iter(app.world())
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(segments.len(), 2 + BONUS_FOOD_SEGMENTS as usize);
+    segments.sort_by_key(|position| position.y);
+    // The head moved up to (3, 4), and the new segments trail below the old
+    // tail in a line.
+    let column = segments.iter().map(|position| (position.x, position.y));
+    assert_eq!(column.collect::<Vec<_>>(), [(3, 1), (3, 2), (3, 3), (3, 4)]);
+    assert_eq!(app.world().resource::<Score>().0, BONUS_FOOD_POINTS);
+}
+
+#[test]
+fn bonus_food_only_lands_on_free_cells() {
+    let mut app = App::new();
+    app.insert_resource(ArenaConfig {
+        width: 5,
+        height: 5,
+        ..default()
+    })
+    .insert_resource(GameRng::new(0))
+    .insert_resource(FoodsSinceBonus(BONUS_FOOD_INTERVAL))
+    .add_systems(Update, spawn_bonus_food);
+    let free = Position { x: 1, y: 2 };
+    for x in 0..5 {
+        for y in 0..5 {
+            if (Position { x, y }) != free {
+                app.world_mut().spawn((SnakeSegment, Position { x, y }));
+            }
+        }
+    }
+    app.update();
+    let bonus = app
+        .world_mut()
+        .query_filtered::<&Position, With<BonusFood>>()
+        .single(app.world());
+    assert_eq!(*bonus, free);
+}
+
+#[test]
+fn crashing_with_lives_left_respawns_the_snake() {
+    let mut app = playing_app();
+    app.world_mut().resource_mut::<Score>().0 = 4;
+    // Put the food where the snake comes back, so it has to move.
+    let (food, _) = app
+        .world_mut()
+        .query_filtered::<(Entity, &Position), With<Food>>()
+        .single(app.world());
+    *app.world_mut().get_mut::<Position>(food).unwrap() = Position { x: 3, y: 3 };
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Playing
+    );
+    assert_eq!(app.world().resource::<Lives>().0, 2);
+    assert_eq!(app.world().resource::<Score>().0, 4);
+    assert_eq!(head_position(&mut app).x, 3);
+    let segments = app
+        .world_mut()
+        .query_filtered::<(), With<SnakeSegment>>()
+        .iter(app.world())
+        .count();
+    assert_eq!(segments, 2);
+    let foods = food_positions(&mut app);
+    assert_eq!(foods.len(), 1);
+    assert!(!foods.contains(&Position { x: 3, y: 3 }));
+}
+
+#[test]
+fn respawning_resets_the_speed() {
+    let mut app = playing_app();
+    for _ in 0..FOODS_PER_SPEED_STEP {
+        let food_cell = head_position(&mut app).step(Direction::Up);
+        let mut foods = app
+            .world_mut()
+            .query_filtered::<&mut Position, With<Food>>();
+        for mut food in foods.iter_mut(app.world_mut()) {
+            *food = food_cell;
+        }
+        step(&mut app);
+    }
+    app.update();
+    let start_period = app.world().resource::<Difficulty>().period(0);
+    assert!(app.world().resource::<MoveTimer>().0.duration() < start_period);
+
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+    assert_eq!(app.world().resource::<Lives>().0, 2);
+    assert_eq!(
+        app.world().resource::<MoveTimer>().0.duration(),
+        start_period
+    );
+}
+
+#[test]
+fn filling_the_board_wins() {
+    let mut app = headless_app();
+    app.insert_resource(ArenaConfig {
+        width: 5,
+        height: 5,
+        obstacles: 0,
+        ..default()
+    });
+    start_round(&mut app);
+    // Leave only the cell in front of the head free, with the last food on it.
+    let food = app
+        .world_mut()
+        .query_filtered::<Entity, With<Food>>()
+        .single(app.world());
+    app.world_mut().despawn(food);
+    let food_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((Food, food_cell));
+    let head = head_position(&mut app);
+    let snake = [head, head.step(Direction::Down)];
+    for x in 0..5 {
+        for y in 0..5 {
+            let cell = Position { x, y };
+            if cell != food_cell && !snake.contains(&cell) {
+                app.world_mut().spawn(cell);
+            }
+        }
+    }
+    step(&mut app);
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Won
+    );
+}
+
+#[test]
+fn hitting_a_wall_on_the_last_life_ends_the_game() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    start_round(&mut app);
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    let events = app.world().resource::<Events<GameOverEvent>>();
+    assert!(!events.is_empty());
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::GameOver
+    );
+}
+
+#[test]
+fn ticks_after_game_over_leave_the_snake_alone() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    start_round(&mut app);
+    app.world_mut().send_event(GameOverEvent);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::GameOver
+    );
+}
+
+#[test]
+fn snake_missing_a_segment_sits_still() {
+    let mut app = playing_app();
+    let tail = *app
+        .world_mut()
+        .query::<&SnakeSegments>()
+        .single(app.world())
+        .0
+        .last()
+        .unwrap();
+    app.world_mut().despawn(tail);
+    let before = head_position(&mut app);
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(head_position(&mut app), before);
+}
+
+/// Curls the snake into a 2x2 square with its head at (3, 3), its body at
+/// (4, 3) and (4, 2) and its tail at (3, 2), then turns the head.
+fn coil_snake(app: &mut App, direction: Direction) {
+    let (mut head, segments) = app
+        .world_mut()
+        .query::<(&mut SnakeHead, &SnakeSegments)>()
+        .single_mut(app.world_mut());
+    head.direction = direction;
+    let neck = segments.0[1];
+    app.world_mut().entity_mut(neck).insert((
+        Position { x: 4, y: 3 },
+        PreviousPosition(Position { x: 4, y: 3 }),
+    ));
+    let tail = [Position { x: 4, y: 2 }, Position { x: 3, y: 2 }].map(|position| {
+        app.world_mut()
+            .spawn((SnakeSegment, position, PreviousPosition(position)))
+            .id()
+    });
+    let mut segments = app
+        .world_mut()
+        .query::<&mut SnakeSegments>()
+        .single_mut(app.world_mut());
+    segments.0.extend(tail);
+    // Keep the food out of the way.
+    let mut foods = app
+        .world_mut()
+        .query_filtered::<&mut Position, With<Food>>();
+    for mut food in foods.iter_mut(app.world_mut()) {
+        *food = Position { x: 8, y: 8 };
+    }
+}
+
+#[test]
+fn chasing_the_tail_is_safe() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    start_round(&mut app);
+    coil_snake(&mut app, Direction::Down);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 2 });
+    assert!(app.world().resource::<Events<GameOverEvent>>().is_empty());
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Playing
+    );
+}
+
+#[test]
+fn running_into_the_body_ends_the_game() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    start_round(&mut app);
+    coil_snake(&mut app, Direction::Right);
+    step(&mut app);
+    let events = app.world().resource::<Events<GameOverEvent>>();
+    assert_eq!(events.len(), 1);
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::GameOver
+    );
+}
+
+#[test]
+fn food_count_tops_the_board_up() {
+    let mut app = headless_app();
+    app.insert_resource(FoodCount(3));
+    start_round(&mut app);
+    let foods = food_positions(&mut app);
+    assert_eq!(foods.len(), 3);
+    assert_distinct(&foods);
+
+    // Eat a food placed right in front of the head.
+    let food_cell = head_position(&mut app).step(Direction::Up);
+    let mut foods = app
+        .world_mut()
+        .query_filtered::<(Entity, &Position), With<Food>>();
+    let (food, _) = foods
+        .iter(app.world())
+        .find(|(_, position)| **position == food_cell)
+        .or_else(|| foods.iter(app.world()).next())
+        .unwrap();
+    *app.world_mut().get_mut::<Position>(food).unwrap() = food_cell;
+    step(&mut app);
+
+    let growths = app.world().resource::<Events<GrowthEvent>>().len();
+    assert_eq!(growths, 1);
+    let foods = food_positions(&mut app);
+    assert_eq!(foods.len(), 3);
+    assert_distinct(&foods);
+}
+
+#[test]
+fn two_foods_eaten_on_one_tick_are_both_replaced() {
+    let mut app = headless_app();
+    app.insert_resource(GameMode::Versus)
+        .insert_resource(FoodCount(3));
+    start_round(&mut app);
+    let one = player_head(&mut app, Player::One);
+    let targets = [
+        one.step(Direction::Up),
+        player_head(&mut app, Player::Two).step(Direction::Down),
+    ];
+    // Move two of the three foods in front of the heads.
+    let foods = app
+        .world_mut()
+        .query_filtered::<Entity, With<Food>>()
+        .iter(app.world())
+        .collect::<Vec<_>>();
+    for (food, target) in foods.into_iter().zip(targets) {
+        *app.world_mut().get_mut::<Position>(food).unwrap() = target;
+    }
+    while player_head(&mut app, Player::One) == one {
+        app.update();
+    }
+
+    assert_eq!(app.world().resource::<Events<GrowthEvent>>().len(), 2);
+    let foods = food_positions(&mut app);
+    assert_eq!(foods.len(), 3);
+    assert_distinct(&foods);
+    assert!(targets.iter().all(|target| !foods.contains(target)));
+}
+
+fn connect_gamepad(app: &mut App) -> Gamepad {
+    let gamepad = Gamepad::new(0);
+    app.world_mut()
+        .send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: "Test pad".to_string(),
+            }),
+        )));
+    app.update();
+    gamepad
+}
+
+fn press_dpad(app: &mut App, gamepad: Gamepad, button: GamepadButtonType) {
+    app.world_mut()
+        .send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(
+            gamepad, button, 1.,
+        )));
+}
+
+fn release_key(app: &mut App, key_code: KeyCode, logical_key: Key) {
+    app.world_mut().send_event(KeyboardInput {
+        key_code,
+        logical_key,
+        state: ButtonState::Released,
+        window: Entity::PLACEHOLDER,
+    });
+}
+
+fn press_key(app: &mut App, key_code: KeyCode, logical_key: Key) {
+    app.world_mut().send_event(KeyboardInput {
+        key_code,
+        logical_key,
+        state: ButtonState::Pressed,
+        window: Entity::PLACEHOLDER,
+    });
+}
+
+fn player_head(app: &mut App, player: Player) -> Position {
+    app.world_mut()
+        .query_filtered::<(&Position, &Player), With<SnakeHead>>()
+        .iter(app.world())
+        .find(|(_, owner)| **owner == player)
+        .map(|(position, _)| *position)
+        .unwrap()
+}
+
+#[test]
+fn ijkl_steers_player_two_in_versus() {
+    let mut app = headless_app();
+    app.insert_resource(GameMode::Versus);
+    start_round(&mut app);
+    let one = player_head(&mut app, Player::One);
+    let two = player_head(&mut app, Player::Two);
+    press_key(&mut app, KeyCode::KeyJ, Key::Character("j".into()));
+    while player_head(&mut app, Player::One) == one {
+        app.update();
+    }
+    assert_eq!(player_head(&mut app, Player::One), one.step(Direction::Up));
+    assert_eq!(
+        player_head(&mut app, Player::Two),
+        two.step(Direction::Left)
+    );
+}
+
+#[test]
+fn versus_snakes_start_apart_on_small_arenas() {
+    for (width, height) in [
+        (MIN_ARENA_SIZE, MIN_ARENA_SIZE),
+        (7, 7),
+        (8, 8),
+        (MIN_ARENA_SIZE, 20),
+    ] {
+        let mut app = headless_app();
+        app.insert_resource(GameMode::Versus)
+            .insert_resource(ArenaConfig {
+                width,
+                height,
+                obstacles: 0,
+                ..default()
+            });
+        start_round(&mut app);
+        let cells = app
+            .world_mut()
+            .query_filtered::<&Position, With<SnakeSegment>>()
+            .iter(app.world())
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(cells.len(), 4);
+        assert_distinct(&cells);
+        let arena = app.world().resource::<ArenaConfig>();
+        assert!(cells.iter().all(|cell| arena.contains(cell)));
+    }
+}
+
+#[test]
+fn dpad_steers_the_snake() {
+    let mut app = playing_app();
+    let gamepad = connect_gamepad(&mut app);
+    let before = head_position(&mut app);
+    press_dpad(&mut app, gamepad, GamepadButtonType::DPadRight);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Right));
+}
+
+fn push_stick(app: &mut App, gamepad: Gamepad, value: f32) {
+    app.world_mut()
+        .send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            value,
+        )));
+}
+
+#[test]
+fn stick_steers_past_the_dead_zone() {
+    let mut app = playing_app();
+    let gamepad = connect_gamepad(&mut app);
+    let before = head_position(&mut app);
+    push_stick(&mut app, gamepad, 0.3);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Up));
+    let before = head_position(&mut app);
+    push_stick(&mut app, gamepad, 0.9);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Right));
+}
+
+#[test]
+fn dead_zone_is_configurable() {
+    let mut app = playing_app();
+    app.insert_resource(StickDeadzone(0.2));
+    let gamepad = connect_gamepad(&mut app);
+    let before = head_position(&mut app);
+    push_stick(&mut app, gamepad, 0.3);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Right));
+}
+
+#[test]
+fn unplugging_a_gamepad_keeps_the_snake_going() {
+    let mut app = playing_app();
+    let gamepad = connect_gamepad(&mut app);
+    push_stick(&mut app, gamepad, 0.9);
+    step(&mut app);
+    app.world_mut()
+        .send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Disconnected,
+        )));
+    let before = head_position(&mut app);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Right));
+}
+
+#[test]
+fn latest_device_wins() {
+    let mut app = playing_app();
+    let gamepad = connect_gamepad(&mut app);
+    press_key(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+    step(&mut app);
+    // The arrow key is still held, but the D-pad press is newer.
+    let before = head_position(&mut app);
+    press_dpad(&mut app, gamepad, GamepadButtonType::DPadLeft);
+    step(&mut app);
+    assert_eq!(head_position(&mut app), before.step(Direction::Left));
+}
+
+#[test]
+fn head_faces_its_direction() {
+    let mut app = playing_app();
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    step(&mut app);
+    let rotation = app
+        .world_mut()
+        .query_filtered::<&Transform, With<SnakeHead>>()
+        .single(app.world())
+        .rotation;
+    assert!(rotation.abs_diff_eq(Quat::from_rotation_z(-FRAC_PI_2), 1e-6));
+}
+
+#[test]
+fn body_darkens_towards_the_tail() {
+    let mut app = playing_app();
+    let bonus_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((
+        BonusFood(Timer::new(Duration::from_secs(60), TimerMode::Once)),
+        bonus_cell,
+    ));
+    step(&mut app);
+    let segments = app
+        .world_mut()
+        .query::<&SnakeSegments>()
+        .single(app.world())
+        .0
+        .clone();
+    let luminance = segments
+        .iter()
+        .skip(1)
+        .map(|segment| {
+            app.world()
+                .get::<Sprite>(*segment)
+                .unwrap()
+                .color
+                .luminance()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(luminance.len(), 1 + BONUS_FOOD_SEGMENTS as usize);
+    assert!(luminance.windows(2).all(|pair| pair[0] > pair[1]));
+}
+
+#[test]
+fn c_cycles_the_theme_and_repaints_the_board() {
+    let mut app = playing_app();
+    press_key(&mut app, KeyCode::KeyC, Key::Character("c".into()));
+    let food_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((Food, food_cell, Sprite::default()));
+    step(&mut app);
+
+    let theme = *app.world().resource::<Theme>();
+    let arena = *app.world().resource::<ArenaConfig>();
+    assert_eq!(theme, Theme::presets(&arena)[1]);
+    assert_eq!(app.world().resource::<ClearColor>().0, theme.background);
+    let head = app
+        .world_mut()
+        .query_filtered::<&Sprite, With<SnakeHead>>()
+        .single(app.world())
+        .color;
+    assert_eq!(head, theme.head);
+    let foods = app
+        .world_mut()
+        .query_filtered::<&Sprite, With<Food>>()
+        .iter(app.world())
+        .map(|sprite| sprite.color)
+        .collect::<Vec<_>>();
+    assert!(!foods.is_empty());
+    assert!(foods.iter().all(|color| *color == theme.food));
+    // The segment grown after the switch is shaded in the new theme too.
+    let tail = *app
+        .world_mut()
+        .query::<&SnakeSegments>()
+        .single(app.world())
+        .0
+        .last()
+        .unwrap();
+    let tail = app.world().get::<Sprite>(tail).unwrap().color.to_srgba();
+    let expected = theme.segment.darker(0.15).to_srgba();
+    assert!(tail.to_vec4().abs_diff_eq(expected.to_vec4(), 1e-4));
+}
+
+#[test]
+fn changed_settings_are_saved() {
+    let mut app = headless_app();
+    // As if passed on the command line, over a file with the defaults.
+    app.insert_resource(Settings::default())
+        .insert_resource(GameRules {
+            arena_mode: ArenaMode::Wrapping,
+            portals: true,
+        })
+        .insert_resource(FoodCount(3));
+    app.update();
+    let path = settings::path().unwrap();
+    // Nothing changed yet, so nothing is written.
+    let _ = std::fs::remove_file(&path);
+    app.update();
+    assert!(!path.exists());
+
+    press_key(&mut app, KeyCode::KeyC, Key::Character("c".into()));
+    press_key(&mut app, KeyCode::KeyM, Key::Character("m".into()));
+    app.update();
+    let saved = settings::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    // Only what changed in game is saved.
+    assert_eq!(
+        saved,
+        Settings {
+            theme: "Forest".to_string(),
+            muted: true,
+            ..default()
+        }
+    );
+}
+
+#[test]
+fn head_glides_between_cells() {
+    let mut app = playing_app();
+    app.world_mut().spawn(Window {
+        resolution: (500., 500.).into(),
+        ..default()
+    });
+    step(&mut app);
+    // A 10x10 arena in a 500 pixel window puts cell centers 50 pixels apart,
+    // and the head just moved from row 3 to row 4.
+    let translation = app
+        .world_mut()
+        .query_filtered::<&Transform, With<SnakeHead>>()
+        .single(app.world())
+        .translation;
+    let center = |cell: f32| cell * 50. - 250. + 25.;
+    assert_eq!(translation.x, center(3.));
+    assert!(
+        center(3.) < translation.y && translation.y < center(4.),
+        "{translation}"
+    );
+}
+
+#[test]
+fn glide_stays_between_cells_with_jittery_frames() {
+    let mut app = playing_app();
+    app.world_mut().spawn(Window {
+        resolution: (500., 500.).into(),
+        ..default()
+    });
+    let mut rng = StdRng::seed_from_u64(3);
+    let center = |cell: i32| cell as f32 * 50. - 250. + 25.;
+    let mut last_y = f32::MIN;
+    // The head heads up from row 3 and stops short of the top edge.
+    while head_position(&mut app).y < 8 {
+        let delta = Duration::from_millis(rng.gen_range(3..=40));
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(delta));
+        app.update();
+        let (transform, position, previous) = app
+            .world_mut()
+            .query_filtered::<(&Transform, &Position, &PreviousPosition), With<SnakeHead>>()
+            .single(app.world());
+        let y = transform.translation.y;
+        assert!(
+            center(previous.0.y) <= y && y <= center(position.y),
+            "{y} outside {previous:?}..{position:?}",
+            previous = previous.0,
+        );
+        assert!(y >= last_y, "glide went back from {last_y} to {y}");
+        last_y = y;
+    }
+}
+
+#[test]
+fn head_snaps_when_it_wraps() {
+    let mut app = headless_app();
+    app.insert_resource(GameRules {
+        arena_mode: ArenaMode::Wrapping,
+        ..default()
+    });
+    start_round(&mut app);
+    app.world_mut().spawn(Window {
+        resolution: (500., 500.).into(),
+        ..default()
+    });
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 0 });
+    let translation = app
+        .world_mut()
+        .query_filtered::<&Transform, With<SnakeHead>>()
+        .single(app.world())
+        .translation;
+    // Mid-glide it would sit between rows 9 and 0; snapped, it is on row 0.
+    let center = |cell: f32| cell * 50. - 250. + 25.;
+    assert_eq!(translation.y, center(0.));
+}
+
+#[test]
+fn difficulty_buttons_light_up_and_start_the_round() {
+    let mut app = headless_app();
+    app.update();
+    let mut buttons = app
+        .world_mut()
+        .query::<(Entity, &DifficultyButton, &BackgroundColor)>();
+    let hard = buttons
+        .iter(app.world())
+        .find(|(_, button, _)| button.0 == DifficultyLevel::Hard)
+        .map(|(entity, _, _)| entity)
+        .unwrap();
+    let selected = buttons
+        .iter(app.world())
+        .filter(|(_, _, color)| color.0 == BUTTON_SELECTED_COLOR)
+        .map(|(_, button, _)| button.0)
+        .collect::<Vec<_>>();
+    assert_eq!(selected, [DifficultyLevel::Normal]);
+
+    app.world_mut()
+        .entity_mut(hard)
+        .insert(Interaction::Hovered);
+    app.update();
+    let color = app.world().get::<BackgroundColor>(hard).unwrap().0;
+    assert_eq!(color, BUTTON_HOVERED_COLOR);
+
+    app.world_mut()
+        .entity_mut(hard)
+        .insert(Interaction::Pressed);
+    app.update();
+    app.update();
+    assert_eq!(
+        *app.world().resource::<DifficultyLevel>(),
+        DifficultyLevel::Hard
+    );
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Playing
+    );
+    let difficulty = app.world().resource::<Difficulty>();
+    assert_eq!(difficulty.step, DifficultyLevel::Hard.period_step());
+    assert!(app
+        .world_mut()
+        .query::<&DifficultyButton>()
+        .iter(app.world())
+        .next()
+        .is_none());
+}
+
+#[test]
+fn speeds_past_the_floor_are_kept() {
+    let difficulty = Difficulty {
+        initial_period: Duration::from_millis(30),
+        step: Duration::from_millis(10),
+        foods_per_step: 2,
+        min_period: MIN_MOVE_PERIOD,
+    };
+    assert!(difficulty.initial_period < MIN_MOVE_PERIOD);
+    assert_eq!(difficulty.period(0), Duration::from_millis(30));
+    assert_eq!(difficulty.period(10), Duration::from_millis(30));
+    let difficulty = Difficulty {
+        initial_period: Duration::from_millis(70),
+        ..difficulty
+    };
+    assert_eq!(difficulty.period(2), Duration::from_millis(60));
+    assert_eq!(difficulty.period(10), MIN_MOVE_PERIOD);
+}
+
+#[test]
+fn menu_difficulty_sets_speed_and_walls() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    app.update();
+    press_key(&mut app, KeyCode::ArrowDown, Key::ArrowDown);
+    app.update();
+    assert_eq!(
+        *app.world().resource::<DifficultyLevel>(),
+        DifficultyLevel::Hard
+    );
+    press_key(&mut app, KeyCode::Enter, Key::Enter);
+    app.update();
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Playing
+    );
+    let period = app.world().resource::<MoveTimer>().0.duration();
+    let tick_secs = app.world().resource::<ArenaConfig>().tick_secs;
+    assert_eq!(period, Duration::from_secs_f32(tick_secs * 0.6));
+
+    // Crash, then head back to the menu and pick the easy preset.
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+    press_key(&mut app, KeyCode::Escape, Key::Escape);
+    app.update();
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Menu
+    );
+    for _ in 0..3 {
+        press_key(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        app.update();
+        release_key(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+        app.update();
+    }
+    assert_eq!(
+        *app.world().resource::<DifficultyLevel>(),
+        DifficultyLevel::Easy
+    );
+    assert_eq!(
+        app.world().resource::<GameRules>().arena_mode,
+        ArenaMode::Wrapping
+    );
+}
+
+fn countdown_text(app: &mut App) -> Option<String> {
+    app.world_mut()
+        .query_filtered::<&Text, With<CountdownText>>()
+        .iter(app.world())
+        .next()
+        .map(|text| text.sections[0].value.clone())
+}
+
+#[test]
+fn countdown_freezes_the_snake_but_lets_it_aim() {
+    let mut app = headless_app();
+    app.insert_resource(StartCountdown::default());
+    start_round(&mut app);
+    // Each update is 200 ms, so three seconds take fifteen of them.
+    app.update();
+    assert_eq!(countdown_text(&mut app).as_deref(), Some("3"));
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    for _ in 0..12 {
+        app.update();
+    }
+    assert_eq!(countdown_text(&mut app).as_deref(), Some("1"));
+    assert_eq!(head_position(&mut app), Position { x: 3, y: 3 });
+
+    step(&mut app);
+    assert_eq!(head_position(&mut app), Position { x: 4, y: 3 });
+    assert_eq!(countdown_text(&mut app).as_deref(), Some("Go!"));
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(countdown_text(&mut app), None);
+}
+
+#[test]
+fn respawning_counts_down_again() {
+    let mut app = headless_app();
+    app.insert_resource(StartCountdown::default());
+    start_round(&mut app);
+    // Run out the opening countdown, then crash into the top wall.
+    while *app.world().resource::<State<RoundPhase>>().get() != RoundPhase::Running {
+        app.update();
+    }
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<RoundPhase>>().get(),
+        RoundPhase::Countdown
+    );
+    assert_eq!(countdown_text(&mut app).as_deref(), Some("1"));
+    let start = head_position(&mut app);
+    // One second of countdown is five updates.
+    for _ in 0..4 {
+        app.update();
+        assert_eq!(head_position(&mut app), start);
+    }
+    step(&mut app);
+    assert_eq!(head_position(&mut app), start.step(Direction::Up));
+}
+
+#[test]
+fn r_restarts_the_round() {
+    let mut app = playing_app();
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    step(&mut app);
+    app.world_mut().resource_mut::<Score>().0 = 4;
+    app.world_mut().send_event(KeyboardInput {
+        key_code: KeyCode::ArrowRight,
+        logical_key: Key::ArrowRight,
+        state: ButtonState::Released,
+        window: Entity::PLACEHOLDER,
+    });
+    press_key(&mut app, KeyCode::KeyR, Key::Character("r".into()));
+    app.update();
+
+    assert_eq!(app.world().resource::<Score>().0, 0);
+    let (head, segments) = app
+        .world_mut()
+        .query::<(&SnakeHead, &SnakeSegments)>()
+        .single(app.world());
+    assert!(head.direction == Direction::Up);
+    assert_eq!(segments.0.len(), 2);
+    let segment_count = app
+        .world_mut()
+        .query_filtered::<(), With<SnakeSegment>>()
+        .iter(app.world())
+        .count();
+    assert_eq!(segment_count, 2);
+    assert_eq!(food_positions(&mut app).len(), 1);
+    assert_eq!(head_position(&mut app).x, 3);
+}
+
+#[test]
+fn restarting_a_replay_or_bot_round_keeps_the_high_score() {
+    let replay = playing_app()
+        .world_mut()
+        .resource_mut::<Recording>()
+        .0
+        .take()
+        .expect("round was not recorded");
+    for watching in [true, false] {
+        let mut app = headless_app();
+        if watching {
+            app.insert_resource(Playback::new(replay.clone()));
+        } else {
+            app.insert_resource(Bot);
+        }
+        start_round(&mut app);
+        app.world_mut().resource_mut::<Score>().0 = 40;
+        press_key(&mut app, KeyCode::KeyR, Key::Character("r".into()));
+        app.update();
+        assert_eq!(app.world().resource::<HighScore>().0, 0);
+    }
+}
+
+#[test]
+fn round_stats_count_up_and_reset_on_restart() {
+    let mut app = playing_app();
+    let food_cell = head_position(&mut app).step(Direction::Up);
+    app.world_mut().spawn((Food, food_cell));
+    step(&mut app);
+    let stats = app.world().resource::<RoundStats>().clone();
+    assert!(stats.elapsed > Duration::ZERO);
+    assert_eq!(stats.foods_eaten, 1);
+    assert_eq!(stats.max_length, 3);
+    let timer = app
+        .world_mut()
+        .query_filtered::<&Text, With<RoundTimerText>>()
+        .single(app.world())
+        .sections[0]
+        .value
+        .clone();
+    assert_eq!(timer, format_time(stats.elapsed));
+    app.update();
+    app.update();
+
+    press_key(&mut app, KeyCode::KeyR, Key::Character("r".into()));
+    app.update();
+    // The new round already ran for this one frame.
+    let restarted = app.world().resource::<RoundStats>();
+    assert!(restarted.elapsed <= Duration::from_millis(200));
+    assert_eq!(restarted.foods_eaten, 0);
+    assert_eq!(restarted.max_length, 2);
+}
+
+#[test]
+fn game_over_screen_shows_the_round_stats() {
+    let mut app = headless_app();
+    app.insert_resource(StartingLives(1));
+    start_round(&mut app);
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+    let summary = app
+        .world()
+        .resource::<RoundStats>()
+        .summary(&SessionBest(None));
+    let texts = app
+        .world_mut()
+        .query::<&Text>()
+        .iter(app.world())
+        .map(|text| text.sections[0].value.clone())
+        .collect::<Vec<_>>();
+    assert!(texts.contains(&summary), "{texts:?}");
+}
+
+#[test]
+fn arena_stays_square_when_the_window_resizes() {
+    let mut app = playing_app();
+    let window = app
+        .world_mut()
+        .spawn((
+            Window {
+                resolution: (1000., 500.).into(),
+                ..default()
+            },
+            PrimaryWindow,
+        ))
+        .id();
+    let background_scale = |app: &mut App| {
+        app.update();
+        app.world_mut()
+            .query_filtered::<&Transform, With<ArenaBackground>>()
+            .iter(app.world())
+            .next()
+            .unwrap()
+            .scale
+    };
+    // The 10x10 arena fills the shorter side.
+    assert_eq!(background_scale(&mut app), Vec3::new(500., 500., 1.));
+    app.world_mut()
+        .get_mut::<Window>(window)
+        .unwrap()
+        .resolution
+        .set(400., 800.);
+    assert_eq!(background_scale(&mut app), Vec3::new(400., 400., 1.));
+}
+
+#[test]
+fn walls_block_the_snake_but_leave_its_lane_open() {
+    let mut app = headless_app();
+    app.insert_resource(ArenaConfig {
+        obstacles: 0,
+        walls: WallPattern::Cross,
+        ..default()
+    });
+    start_round(&mut app);
+    let walls = app
+        .world_mut()
+        .query_filtered::<&Position, (With<Wall>, With<Obstacle>)>()
+        .iter(app.world())
+        .copied()
+        .collect::<Vec<Position>>();
+    assert!(!walls.is_empty());
+    assert!(walls.iter().all(|wall| wall.x != 3));
+    assert!(!food_positions(&mut app)
+        .iter()
+        .any(|food| walls.contains(food)));
+
+    // The cross runs along row 5, so turning right on row 5 hits it.
+    set_head_position(&mut app, Position { x: 3, y: 5 });
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    step(&mut app);
+    assert!(!app.world().resource::<Events<GameOverEvent>>().is_empty());
+}
+
+fn portal_app() -> App {
+    let mut app = headless_app();
+    app.insert_resource(GameRules {
+        portals: true,
+        ..default()
+    })
+    .insert_resource(StartingLives(1));
+    start_round(&mut app);
+    app
+}
+
+/// Moves the round's portals to `entrance` and `exit`, and any food out of
+/// the way.
+fn place_portals(app: &mut App, entrance: Position, exit: Position) {
+    let mut portals = app.world_mut().query::<(&mut Position, &mut Portal)>();
+    for ((mut position, mut portal), (cell, linked)) in portals
+        .iter_mut(app.world_mut())
+        .zip([(entrance, exit), (exit, entrance)])
+    {
+        *position = cell;
+        portal.exit = linked;
+    }
+    let mut foods = app
+        .world_mut()
+        .query_filtered::<&mut Position, With<Food>>();
+    for mut food in foods.iter_mut(app.world_mut()) {
+        *food = Position { x: 0, y: 9 };
+    }
+}
+
+#[test]
+fn portals_only_appear_when_turned_on() {
+    let mut app = playing_app();
+    let mut portals = app.world_mut().query::<&Portal>();
+    assert_eq!(portals.iter(app.world()).count(), 0);
+
+    let mut app = portal_app();
+    let mut portals = app.world_mut().query::<(&Position, &Portal)>();
+    let pair = portals
+        .iter(app.world())
+        .map(|(position, portal)| (*position, portal.exit))
+        .collect::<Vec<_>>();
+    assert_eq!(pair.len(), 2);
+    assert_eq!(pair[0], (pair[1].1, pair[1].0));
+    let mut others = app
+        .world_mut()
+        .query_filtered::<&Position, (Without<Portal>, Without<ArenaBackground>)>();
+    for position in others.iter(app.world()) {
+        assert!(pair.iter().all(|(portal, _)| portal != position));
+    }
+}
+
+#[test]
+fn portal_sends_the_head_out_of_its_twin() {
+    let mut app = portal_app();
+    place_portals(&mut app, Position { x: 3, y: 4 }, Position { x: 7, y: 6 });
+    let exit_cell = Position { x: 7, y: 7 };
+    app.world_mut().spawn((Food, exit_cell));
+    step(&mut app);
+    assert_eq!(head_position(&mut app), exit_cell);
+    // The food past the exit counts on the tick the head comes out.
+    assert_eq!(app.world().resource::<Score>().0, 1);
+    app.update();
+    let segments = app
+        .world_mut()
+        .query::<&SnakeSegments>()
+        .single(app.world())
+        .0
+        .len();
+    assert_eq!(segments, 3);
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::Playing
+    );
+}
+
+#[test]
+fn coming_out_of_a_portal_into_the_edge_ends_the_game() {
+    let mut app = portal_app();
+    place_portals(&mut app, Position { x: 3, y: 4 }, Position { x: 7, y: 9 });
+    step(&mut app);
+    let events = app.world().resource::<Events<GameOverEvent>>();
+    assert!(!events.is_empty());
+    app.update();
+    assert_eq!(
+        *app.world().resource::<State<GameState>>().get(),
+        GameState::GameOver
+    );
+}
+
+#[test]
+fn checkerboard_survives_the_round() {
+    let mut app = playing_app();
+    let tiles = |app: &mut App| {
+        app.world_mut()
+            .query_filtered::<(), With<ArenaBackground>>()
+            .iter(app.world())
+            .count()
+    };
+    assert_eq!(tiles(&mut app), 1);
+    set_head_position(&mut app, Position { x: 3, y: 9 });
+    step(&mut app);
+    app.update();
+    start_round(&mut app);
+    assert_eq!(tiles(&mut app), 1);
+}
+
+#[test]
+fn grid_lines_follow_the_window_and_toggle_with_g() {
+    let mut app = playing_app();
+    let window = app
+        .world_mut()
+        .spawn((
+            Window {
+                resolution: (500., 500.).into(),
+                ..default()
+            },
+            PrimaryWindow,
+        ))
+        .id();
+    app.update();
+    // Eleven boundaries each way around ten cells.
+    let mut lines = app.world_mut().query_filtered::<(), With<GridLine>>();
+    assert_eq!(lines.iter(app.world()).count(), 22);
+    let left_edge = |app: &mut App| {
+        let mut lines = app.world_mut().query::<(&GridLine, &Transform)>();
+        lines
+            .iter(app.world())
+            .find(|(line, _)| line.vertical && line.index == 0)
+            .unwrap()
+            .1
+            .translation
+            .x
+    };
+    assert_eq!(left_edge(&mut app), -250.);
+
+    app.world_mut()
+        .get_mut::<Window>(window)
+        .unwrap()
+        .resolution
+        .set(400., 500.);
+    app.update();
+    assert_eq!(left_edge(&mut app), -200.);
+
+    press_key(&mut app, KeyCode::KeyG, Key::Character("g".into()));
+    app.update();
+    let mut visibilities = app
+        .world_mut()
+        .query_filtered::<&Visibility, With<GridLine>>();
+    assert!(visibilities
+        .iter(app.world())
+        .all(|visibility| *visibility == Visibility::Hidden));
+}
+
+fn seeded_food(seed: u64) -> Vec<Position> {
+    let mut app = headless_app();
+    app.insert_resource(GameRng::new(seed))
+        .insert_resource(FoodCount(5));
+    start_round(&mut app);
+    food_positions(&mut app)
+}
+
+/// Runs `spawn_food` on its own against an empty board, clearing the board
+/// after each placement.
+fn spawn_food_alone(seed: u64, rounds: usize) -> Vec<Position> {
+    let mut app = App::new();
+    app.add_event::<SpawnEvent>()
+        .add_event::<GrowthEvent>()
+        .init_resource::<ArenaConfig>()
+        .insert_resource(FoodCount(1))
+        .insert_resource(GameRng::new(seed))
+        .init_resource::<Theme>()
+        .add_systems(Update, spawn_food);
+    let mut placed = Vec::new();
+    for _ in 0..rounds {
+        app.world_mut().send_event(SpawnEvent);
+        app.update();
+        let (food, position) = app
+            .world_mut()
+            .query_filtered::<(Entity, &Position), With<Food>>()
+            .single(app.world());
+        placed.push(*position);
+        app.world_mut().despawn(food);
+    }
+    placed
+}
+
+#[test]
+fn seeded_food_lands_on_the_same_cells() {
+    let placed = spawn_food_alone(42, 3);
+    assert_eq!(placed, spawn_food_alone(42, 3));
+    assert_eq!(
+        placed,
+        [
+            Position { x: 1, y: 5 },
+            Position { x: 2, y: 5 },
+            Position { x: 6, y: 4 },
+        ]
+    );
+}
+
+#[test]
+fn seed_decides_food_placement() {
+    assert_eq!(seeded_food(42), seeded_food(42));
+    assert_ne!(seeded_food(42), seeded_food(7));
+}
+
+fn sounds_played(app: &mut App) -> usize {
+    app.world_mut()
+        .query::<&PlaybackSettings>()
+        .iter(app.world())
+        .count()
+}
+
+#[test]
+fn turning_ticks_unless_muted() {
+    let mut app = playing_app();
+    app.insert_resource(GameSounds {
+        eat: Handle::default(),
+        turn: Handle::default(),
+        death: Handle::default(),
+    });
+    step(&mut app);
+    assert_eq!(sounds_played(&mut app), 0);
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    step(&mut app);
+    assert_eq!(sounds_played(&mut app), 1);
+
+    press_key(&mut app, KeyCode::KeyM, Key::Character("m".into()));
+    release_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    press_key(&mut app, KeyCode::ArrowUp, Key::ArrowUp);
+    step(&mut app);
+    assert!(app.world().resource::<SoundSettings>().muted);
+    assert_eq!(sounds_played(&mut app), 1);
+}
+
+#[test]
+fn missing_sounds_leave_the_game_silent() {
+    let mut app = headless_app();
+    app.add_plugins(AssetPlugin {
+        file_path: "no-such-assets".to_string(),
+        ..default()
+    })
+    .init_asset::<AudioSource>();
+    for _ in 0..100 {
+        app.update();
+        if !app.world().contains_resource::<GameSounds>() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("sounds that failed to load were kept");
+}
+
+/// Head and food positions after every update until the round ends.
+fn play_until_game_over(app: &mut App) -> Vec<(Position, Vec<Position>)> {
+    let mut history = Vec::new();
+    for _ in 0..50 {
+        app.update();
+        if *app.world().resource::<State<GameState>>().get() == GameState::GameOver {
+            return history;
+        }
+        history.push((head_position(app), food_positions(app)));
+    }
+    panic!("round never ended");
+}
+
+#[test]
+fn replay_plays_the_round_again() {
+    let mut app = playing_app();
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    let played = play_until_game_over(&mut app);
+    let recording = app.world_mut().resource_mut::<Recording>().0.take();
+    let replay = recording.expect("round was not recorded");
+
+    let mut app = headless_app();
+    app.insert_resource(GameRng::new(7))
+        .insert_resource(Playback::new(replay));
+    start_round(&mut app);
+    assert_eq!(play_until_game_over(&mut app), played);
+}
+
+#[test]
+fn bot_plays_round_after_round() {
+    let mut app = headless_app();
+    app.insert_resource(Bot).insert_resource(StartingLives(1));
+    let mut best = 0;
+    let mut rounds = 0;
+    let mut was_playing = false;
+    for _ in 0..500 {
+        app.update();
+        let playing = *app.world().resource::<State<GameState>>().get() == GameState::Playing;
+        if was_playing && !playing {
+            rounds += 1;
+        }
+        was_playing = playing;
+        best = best.max(app.world().resource::<Score>().0);
+    }
+    assert!(best > 0, "the bot never ate");
+    assert!(rounds > 0, "the bot never finished a round");
+}
+
+#[test]
+fn paused_replay_moves_one_tick_per_step() {
+    let mut app = playing_app();
+    press_key(&mut app, KeyCode::ArrowRight, Key::ArrowRight);
+    play_until_game_over(&mut app);
+    let replay = app.world_mut().resource_mut::<Recording>().0.take();
+
+    let mut app = headless_app();
+    app.insert_resource(Playback::new(replay.expect("round was not recorded")));
+    start_round(&mut app);
+    step(&mut app);
+    press_key(&mut app, KeyCode::Space, Key::Space);
+    app.update();
+    let paused_at = head_position(&mut app);
+    app.update();
+    app.update();
+    assert_eq!(head_position(&mut app), paused_at);
+
+    press_key(&mut app, KeyCode::Period, Key::Character(".".into()));
+    app.update();
+    let stepped_to = head_position(&mut app);
+    assert_ne!(stepped_to, paused_at);
+    app.update();
+    assert_eq!(head_position(&mut app), stepped_to);
+
+    release_key(&mut app, KeyCode::Space, Key::Space);
+    press_key(&mut app, KeyCode::Space, Key::Space);
+    step(&mut app);
+}